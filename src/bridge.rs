@@ -0,0 +1,169 @@
+//! Forwarding frames between two CAN buses.
+
+use std::{fmt, io};
+use std::os::unix::io::AsRawFd;
+
+use log::debug;
+
+use frame::CanFrame;
+use socket::CanSocket;
+use task::ShouldRetry;
+use util::{get_socket_option, is_tx_queue_full};
+
+/// A gateway forwarding frames between two sockets in both directions.
+///
+/// Frames received on either socket are written to the other one, after
+/// passing them through an optional transform. Error frames describe the
+/// state of the bus they were received on and are never forwarded.
+///
+/// If the transmit queue of the other bus is full, the frame is dropped
+/// rather than stalling the opposite direction.
+///
+/// ```no_run
+/// use socketcan::bridge::Bridge;
+/// use socketcan::socket::CanSocket;
+///
+/// let mut bridge = Bridge::new(CanSocket::open("can0").unwrap(),
+///                              CanSocket::open("can1").unwrap())
+///     .with_transform(|frame| if frame.id() < 0x700 { Some(frame) } else { None });
+/// bridge.run().unwrap();
+/// ```
+pub struct Bridge {
+    sockets: [CanSocket; 2],
+    transform: Option<Box<dyn FnMut(CanFrame) -> Option<CanFrame>>>,
+}
+
+impl Bridge {
+    /// Bridge two sockets, forwarding all frames unchanged.
+    pub fn new(a: CanSocket, b: CanSocket) -> Bridge {
+        Bridge {
+            sockets: [a, b],
+            transform: None,
+        }
+    }
+
+    /// Filter or rewrite frames in flight.
+    ///
+    /// The transform is called for frames travelling in either direction.
+    /// Returning `None` drops the frame.
+    pub fn with_transform<F>(mut self, transform: F) -> Bridge
+        where F: FnMut(CanFrame) -> Option<CanFrame> + 'static
+    {
+        self.transform = Some(Box::new(transform));
+        self
+    }
+
+    /// Forward frames until an error occurs.
+    ///
+    /// Waits on both sockets using `poll`, so neither direction can starve
+    /// the other. Returns an error once either socket hangs up, e.g. because
+    /// its interface has been removed.
+    pub fn run(&mut self) -> io::Result<()> {
+        let mut fds = [libc::pollfd {
+                           fd: self.sockets[0].as_raw_fd(),
+                           events: libc::POLLIN,
+                           revents: 0,
+                       },
+                       libc::pollfd {
+                           fd: self.sockets[1].as_raw_fd(),
+                           events: libc::POLLIN,
+                           revents: 0,
+                       }];
+
+        loop {
+            let r = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) };
+
+            if r == -1 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+
+            for (from, pfd) in fds.iter().enumerate() {
+                if pfd.revents & libc::POLLNVAL != 0 {
+                    return Err(io::Error::from_raw_os_error(libc::EBADF));
+                }
+                if pfd.revents & libc::POLLHUP != 0 {
+                    return Err(io::Error::new(io::ErrorKind::BrokenPipe, "socket hung up"));
+                }
+
+                // POLLERR alone is not readable, reading would block
+                if pfd.revents & libc::POLLIN != 0 {
+                    self.forward(from)?;
+                } else if pfd.revents & libc::POLLERR != 0 {
+                    self.clear_error(from)?;
+                }
+            }
+        }
+    }
+
+    /// Move a single frame from socket `from` to the other socket.
+    fn forward(&mut self, from: usize) -> io::Result<()> {
+        let mut frame = CanFrame::empty();
+        match self.sockets[from].read_into(&mut frame) {
+            Ok(()) => (),
+            Err(ref e) if e.should_retry() => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        if frame.is_error() {
+            return Ok(());
+        }
+
+        let frame = match self.transform {
+            Some(ref mut transform) => transform(frame),
+            None => Some(frame),
+        };
+
+        let frame = match frame {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+
+        match self.sockets[1 - from].write(&frame) {
+            Err(ref e) if e.should_retry() || is_tx_queue_full(e) => {
+                debug!("Dropping {:X}, transmit queue full", frame);
+                Ok(())
+            }
+            r => r,
+        }
+    }
+
+    /// Clear the `POLLERR` condition of socket `i`.
+    fn clear_error(&self, i: usize) -> io::Result<()> {
+        let socket = &self.sockets[i];
+
+        // timestamps queued by `CanSocket::enable_tx_timestamps`
+        loop {
+            match socket.recv_tx_timestamp() {
+                Ok(_) => (),
+                Err(ref e) if e.kind() == io::ErrorKind::InvalidData => (),
+                Err(ref e) if e.should_retry() => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let err: libc::c_int = get_socket_option(socket.as_raw_fd(), libc::SOL_SOCKET, libc::SO_ERROR)?;
+        match err {
+            0 => Ok(()),
+            err => Err(io::Error::from_raw_os_error(err)),
+        }
+    }
+
+    /// Unwrap the two bridged sockets.
+    pub fn into_inner(self) -> (CanSocket, CanSocket) {
+        let [a, b] = self.sockets;
+        (a, b)
+    }
+}
+
+impl fmt::Debug for Bridge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Bridge")
+            .field("sockets", &self.sockets)
+            .field("transform", &self.transform.is_some())
+            .finish()
+    }
+}
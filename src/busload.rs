@@ -0,0 +1,259 @@
+//! Bus load estimation and flood detection from observed frames.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use constants::RTR_FLAG;
+use frame::{AnyFrame, CanFrame};
+
+/// Bits of a classic frame excluding payload and stuff bits: SOF, id, RTR,
+/// IDE, r0, DLC, CRC, delimiters, ACK, EOF and interframe space.
+const CLASSIC_SFF_OVERHEAD: u32 = 47;
+const CLASSIC_EFF_OVERHEAD: u32 = 67;
+/// Bits of a classic frame subject to bit stuffing, excluding payload.
+const CLASSIC_SFF_STUFFED: u32 = 34;
+const CLASSIC_EFF_STUFFED: u32 = 54;
+
+/// Bits of the FD arbitration phase up to and including BRS.
+const FD_SFF_ARBITRATION: u32 = 17;
+const FD_EFF_ARBITRATION: u32 = 36;
+/// Bits of the FD data phase excluding payload and CRC: ESI, DLC, stuff count
+/// and CRC delimiter.
+const FD_DATA_OVERHEAD: u32 = 10;
+/// ACK, EOF and interframe space, sent at the nominal bitrate.
+const FD_TRAILER: u32 = 12;
+
+/// BusLoadEstimator
+///
+/// Estimates bus utilization by summing the transmission time of observed
+/// frames over a sliding window. Stuff bits are estimated with their worst
+/// case of one bit for every four bits, so the load of typical traffic is
+/// slightly overestimated.
+#[derive(Debug, Clone)]
+pub struct BusLoadEstimator {
+    bitrate: u32,
+    data_bitrate: u32,
+    window: Duration,
+    frames: VecDeque<(Instant, Duration)>,
+    busy: Duration,
+}
+
+impl BusLoadEstimator {
+    /// Construct an estimator for a bus running at `bitrate` bit/s,
+    /// averaging over `window`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bitrate` is zero.
+    pub fn new(bitrate: u32, window: Duration) -> BusLoadEstimator {
+        assert!(bitrate > 0, "bitrate must not be zero");
+
+        BusLoadEstimator {
+            bitrate,
+            data_bitrate: bitrate,
+            window,
+            frames: VecDeque::new(),
+            busy: Duration::from_secs(0),
+        }
+    }
+
+    /// Set the CAN FD data phase bitrate, used for frames with the bit rate
+    /// switch set. Defaults to the nominal bitrate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data_bitrate` is zero.
+    pub fn with_data_bitrate(mut self, data_bitrate: u32) -> BusLoadEstimator {
+        assert!(data_bitrate > 0, "data bitrate must not be zero");
+
+        self.data_bitrate = data_bitrate;
+        self
+    }
+
+    /// Account for a frame observed now.
+    pub fn push(&mut self, frame: &AnyFrame) {
+        self.push_at(frame, Instant::now())
+    }
+
+    /// Account for a frame observed at `at`, e.g. its receive timestamp.
+    ///
+    /// Frames must be pushed in chronological order.
+    pub fn push_at(&mut self, frame: &AnyFrame, at: Instant) {
+        let duration = self.frame_duration(frame);
+
+        self.frames.push_back((at, duration));
+        self.busy += duration;
+        self.expire(at);
+    }
+
+    /// Bus load in percent over the window ending now.
+    pub fn load(&mut self) -> f32 {
+        self.load_at(Instant::now())
+    }
+
+    /// Bus load in percent over the window ending at `now`.
+    pub fn load_at(&mut self, now: Instant) -> f32 {
+        self.expire(now);
+
+        let window = self.window.as_secs_f32();
+        if window == 0.0 {
+            return 0.0;
+        }
+
+        self.busy.as_secs_f32() / window * 100.0
+    }
+
+    /// Time a frame occupies the bus.
+    pub fn frame_duration(&self, frame: &AnyFrame) -> Duration {
+        match *frame {
+            AnyFrame::Classic(ref f) => {
+                let payload = if f.is_rtr() { 0 } else { 8 * f.data().len() as u32 };
+                let (overhead, stuffed) = if f.is_extended() {
+                    (CLASSIC_EFF_OVERHEAD, CLASSIC_EFF_STUFFED)
+                } else {
+                    (CLASSIC_SFF_OVERHEAD, CLASSIC_SFF_STUFFED)
+                };
+
+                bit_time(overhead + payload + (stuffed + payload - 1) / 4, self.bitrate)
+            }
+            AnyFrame::Fd(ref f) => {
+                let payload = 8 * f.data().len() as u32;
+                let arbitration = if f.is_extended() { FD_EFF_ARBITRATION } else { FD_SFF_ARBITRATION };
+                let crc = if f.data().len() > 16 { 21 } else { 17 };
+
+                // dynamic stuff bits for ESI, DLC and payload, fixed ones within the CRC
+                let arbitration = arbitration + arbitration / 4;
+                let data = FD_DATA_OVERHEAD + payload + crc + (payload + 5) / 4 + crc.div_ceil(4);
+                let data_bitrate = if f.is_brs() { self.data_bitrate } else { self.bitrate };
+
+                bit_time(arbitration + FD_TRAILER, self.bitrate) + bit_time(data, data_bitrate)
+            }
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(&(at, duration)) = self.frames.front() {
+            if now.saturating_duration_since(at) < self.window {
+                break;
+            }
+
+            self.busy -= duration;
+            self.frames.pop_front();
+        }
+    }
+}
+
+fn bit_time(bits: u32, bitrate: u32) -> Duration {
+    Duration::from_nanos(bits as u64 * 1_000_000_000 / bitrate as u64)
+}
+
+/// Reported by `FloodDetector` when an id exceeds the configured rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloodAlert {
+    /// Id of the flooding frames
+    pub id: u32,
+    /// Whether `id` is an extended id
+    pub extended: bool,
+    /// Observed rate in frames per second
+    pub rate: f32,
+}
+
+/// FloodDetector
+///
+/// Tracks the rate of every id over a sliding window and reports ids sent
+/// faster than a threshold, e.g. a node with a high priority id starving the
+/// rest of the bus. An alert is raised once when an id starts flooding and
+/// again only after its rate has dropped back to the threshold.
+#[derive(Debug, Clone)]
+pub struct FloodDetector {
+    max_rate: f32,
+    window: Duration,
+    ids: HashMap<u32, IdRate>,
+    last_sweep: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct IdRate {
+    frames: VecDeque<Instant>,
+    flooding: bool,
+}
+
+impl FloodDetector {
+    /// Construct a detector alerting on ids sent at more than `max_rate`
+    /// frames per second, averaged over `window`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    pub fn new(max_rate: f32, window: Duration) -> FloodDetector {
+        assert!(window > Duration::from_secs(0), "window must not be zero");
+
+        FloodDetector {
+            max_rate,
+            window,
+            ids: HashMap::new(),
+            last_sweep: None,
+        }
+    }
+
+    /// Account for a frame observed now.
+    pub fn feed(&mut self, frame: &CanFrame) -> Option<FloodAlert> {
+        self.feed_at(frame, Instant::now())
+    }
+
+    /// Account for a frame observed at `at`, e.g. its receive timestamp.
+    ///
+    /// Frames must be fed in chronological order. Error frames are ignored.
+    /// Ids not seen for a whole window are forgotten.
+    pub fn feed_at(&mut self, frame: &CanFrame, at: Instant) -> Option<FloodAlert> {
+        if frame.is_error() {
+            return None;
+        }
+
+        let window = self.window;
+
+        // once per window, drop ids whose frames have all left the window
+        match self.last_sweep {
+            Some(last) if at.saturating_duration_since(last) < window => (),
+            _ => {
+                self.ids.retain(|_, entry| {
+                    entry.frames.back().is_some_and(|&last| at.saturating_duration_since(last) < window)
+                });
+                self.last_sweep = Some(at);
+            }
+        }
+
+        // remote requests count towards the rate of their id
+        let entry = self.ids.entry(frame.raw_id() & !RTR_FLAG).or_default();
+
+        entry.frames.push_back(at);
+        while let Some(&first) = entry.frames.front() {
+            if at.saturating_duration_since(first) < window {
+                break;
+            }
+            entry.frames.pop_front();
+        }
+
+        let rate = entry.frames.len() as f32 / window.as_secs_f32();
+        if rate <= self.max_rate {
+            entry.flooding = false;
+            return None;
+        }
+
+        if entry.flooding {
+            return None;
+        }
+
+        entry.flooding = true;
+        Some(FloodAlert {
+            id: frame.id(),
+            extended: frame.is_extended(),
+            rate,
+        })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn tracked_ids(&self) -> usize {
+        self.ids.len()
+    }
+}
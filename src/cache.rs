@@ -0,0 +1,150 @@
+use alloc::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+use frame::CanFrame;
+
+/// FrameCache
+///
+/// Keeps the latest data frame received for every CAN ID, detecting changes.
+/// Frames are keyed by `CanFrame::raw_id()`, so a standard and an extended
+/// frame with the same numeric id are kept apart. Error frames and remote
+/// requests carry no data to cache and are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct FrameCache {
+    frames: BTreeMap<u32, CanFrame>,
+}
+
+impl FrameCache {
+    /// Construct an empty cache.
+    pub fn new() -> FrameCache {
+        FrameCache::default()
+    }
+
+    /// Store a frame, replacing the previous frame with the same id.
+    ///
+    /// Returns `true` if the frame differs from the one previously stored
+    /// (or if none was stored), `false` if it is identical or was ignored
+    /// as an error or remote frame.
+    pub fn update(&mut self, frame: CanFrame) -> bool {
+        if frame.is_error() || frame.is_rtr() {
+            return false;
+        }
+
+        match self.frames.insert(frame.raw_id(), frame) {
+            Some(previous) => previous != frame,
+            None => true,
+        }
+    }
+
+    /// Latest frame stored for `id`.
+    ///
+    /// Like `CanFrame::raw_id()`, extended ids have the EFF flag (0x80000000)
+    /// set.
+    pub fn get(&self, id: u32) -> Option<&CanFrame> {
+        self.frames.get(&id)
+    }
+
+    /// Number of distinct ids stored.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Check if no frames have been stored.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Remove all stored frames.
+    pub fn clear(&mut self) {
+        self.frames.clear()
+    }
+}
+
+/// MuxCollector
+///
+/// Collects multiplexed frames sent under a single CAN ID, keeping the latest
+/// frame for every value of the multiplexer byte. Frames with other ids or
+/// too short to contain the multiplexer are ignored.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct MuxCollector {
+    id: u32,
+    mux_byte: usize,
+    timeout: Option<Duration>,
+    frames: HashMap<u8, CanFrame>,
+    received: HashMap<u8, Instant>,
+}
+
+#[cfg(feature = "std")]
+impl MuxCollector {
+    /// Collect frames with `id`, multiplexed by the data byte at `mux_byte`.
+    pub fn new(id: u32, mux_byte: usize) -> MuxCollector {
+        MuxCollector {
+            id,
+            mux_byte,
+            timeout: None,
+            frames: HashMap::new(),
+            received: HashMap::new(),
+        }
+    }
+
+    /// Drop frames not updated within `timeout`.
+    ///
+    /// Stale frames are removed whenever a new frame is fed.
+    pub fn with_timeout(mut self, timeout: Duration) -> MuxCollector {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Store a frame under its multiplexer value.
+    ///
+    /// Returns `true` if the frame was stored.
+    pub fn feed(&mut self, frame: CanFrame) -> bool {
+        self.feed_at(frame, Instant::now())
+    }
+
+    /// Store a frame received at `at`, e.g. its receive timestamp.
+    pub fn feed_at(&mut self, frame: CanFrame, at: Instant) -> bool {
+        if let Some(timeout) = self.timeout {
+            let frames = &mut self.frames;
+            self.received.retain(|mux, received| {
+                let fresh = at.saturating_duration_since(*received) < timeout;
+                if !fresh {
+                    frames.remove(mux);
+                }
+                fresh
+            });
+        }
+
+        if frame.id() != self.id || frame.is_error() || frame.is_rtr() {
+            return false;
+        }
+
+        let mux = match frame.data().get(self.mux_byte) {
+            Some(&mux) => mux,
+            None => return false,
+        };
+
+        self.frames.insert(mux, frame);
+        self.received.insert(mux, at);
+        true
+    }
+
+    /// Latest frame for every multiplexer value seen.
+    pub fn snapshot(&self) -> &HashMap<u8, CanFrame> {
+        &self.frames
+    }
+
+    /// Check if frames for all `muxes` have been collected.
+    pub fn is_complete(&self, muxes: &[u8]) -> bool {
+        muxes.iter().all(|mux| self.frames.contains_key(mux))
+    }
+
+    /// Remove all collected frames.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.received.clear();
+    }
+}
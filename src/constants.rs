@@ -1,9 +1,14 @@
+#![cfg_attr(not(feature = "std"), allow(dead_code))]
+
 // Protocol of the PF_CAN Family: Standard?
 pub const CAN_RAW: libc::c_int = 1;
 
 // Protool of the PF_CAN Family: Broadcast Manager
 //const CAN_BCM: libc::c_int = 2;
 
+// Protocol of the PF_CAN Family: SAE J1939
+pub const CAN_J1939: libc::c_int = 7;
+
 pub const SOL_CAN_BASE: libc::c_int = 100;
 pub const SOL_CAN_RAW: libc::c_int = SOL_CAN_BASE + CAN_RAW;
 pub const CAN_RAW_FILTER: libc::c_int = 1;
@@ -11,10 +16,47 @@ pub const CAN_RAW_ERR_FILTER: libc::c_int = 2;
 pub const CAN_RAW_LOOPBACK: libc::c_int = 3;
 pub const CAN_RAW_RECV_OWN_MSGS: libc::c_int = 4;
 pub const CAN_RAW_JOIN_FILTERS: libc::c_int = 6;
-// const CAN_RAW_FD_FRAMES: c_int = 5;
+pub const CAN_RAW_FD_FRAMES: libc::c_int = 5;
+pub const CAN_RAW_XL_FRAMES: libc::c_int = 7;
+
+/// maximum number of filters accepted by CAN_RAW_FILTER
+pub const CAN_RAW_FILTER_MAX: usize = 512;
+
+pub const SOL_CAN_J1939: libc::c_int = SOL_CAN_BASE + CAN_J1939;
+pub const SO_J1939_PROMISC: libc::c_int = 2;
+
+/// J1939 "no name", "no parameter group" and "no/broadcast address" values
+pub const J1939_NO_NAME: u64 = 0;
+pub const J1939_NO_PGN: u32 = 0x40000;
+pub const J1939_NO_ADDR: u8 = 0xff;
+/// largest valid J1939 parameter group number
+pub const J1939_PGN_MAX: u32 = 0x3ffff;
+/// largest payload of the J1939 transport protocol
+pub const J1939_MAX_TP_PACKET_SIZE: usize = 7 * 255;
+
+// hardware type of CAN network interfaces
+pub const ARPHRD_CAN: u16 = 280;
+
+// rtnetlink attribute holding the interface name
+pub const IFLA_IFNAME: u16 = 3;
+// rtnetlink attributes nesting the CAN controller mode
+pub const IFLA_LINKINFO: u16 = 18;
+pub const IFLA_INFO_DATA: u16 = 2;
+pub const IFLA_CAN_CTRLMODE: u16 = 5;
+// controller mode without automatic retransmission
+pub const CAN_CTRLMODE_ONE_SHOT: u32 = 0x08;
+
+// netlink message types and flags
+pub const NLMSG_ERROR: u16 = 2;
+pub const NLMSG_DONE: u16 = 3;
+pub const NLM_F_REQUEST: u16 = 1;
+pub const NLM_F_ACK: u16 = 4;
+// NLM_F_ROOT | NLM_F_MATCH, return all entries
+pub const NLM_F_DUMP: u16 = 0x300;
 
 // get timestamp from ioctl in a struct timespec (ns accuracy)
-//pub const SIOCGSTAMPNS: libc::c_int = 0x8907;
+pub const SIOCGSTAMPNS: libc::c_int = 0x8907;
+// get timestamp from ioctl in a struct timeval (us accuracy)
 pub const SIOCGSTAMP: libc::c_int = 0x8906;
 
 /// Special address description flags for the CAN_ID
@@ -26,6 +68,9 @@ pub const RTR_FLAG: u32 = 0x40000000;
 /// error message frame
 pub const ERR_FLAG: u32 = 0x20000000;
 
+/// filter flag inverting the match (set in the filter id)
+pub const INV_FILTER: u32 = 0x20000000;
+
 /// valid bits in CAN ID for frame formats
 /// standard frame format (SFF)
 pub const SFF_MASK: u32 = 0x000007ff;
@@ -34,8 +79,35 @@ pub const EFF_MASK: u32 = 0x1fffffff;
 /// omit EFF, RTR, ERR flags
 pub const ERR_MASK: u32 = 0x1fffffff;
 
-// an error mask that will cause SocketCAN to report all errors
-// pub const ERR_MASK_ALL: u32 = ERR_MASK;
+/// an error mask that will cause SocketCAN to report all errors
+pub const ERR_MASK_ALL: u32 = ERR_MASK;
+
+/// an error mask that will cause SocketCAN to silently drop all errors
+pub const ERR_MASK_NONE: u32 = 0;
+
+/// size of a classic CAN frame as read from a socket
+pub const CAN_MTU: usize = 16;
+/// size of a CAN FD frame as read from a socket
+pub const CANFD_MTU: usize = 72;
+/// maximum CAN FD payload length
+pub const CANFD_MAX_DLEN: usize = 64;
+
+/// bit rate switch (second bitrate for payload data)
+pub const CANFD_BRS: u8 = 0x01;
+/// error state indicator of the transmitting node
+pub const CANFD_ESI: u8 = 0x02;
+
+/// CAN XL payload length limits
+pub const CANXL_MIN_DLEN: usize = 1;
+pub const CANXL_MAX_DLEN: usize = 2048;
+/// size of the CAN XL frame header (everything before the data)
+pub const CANXL_HDR_SIZE: usize = 12;
 
-// an error mask that will cause SocketCAN to silently drop all errors
-// pub const ERR_MASK_NONE: u32 = 0;
+/// mandatory CAN XL frame flag (must always be set)
+pub const CANXL_XLF: u8 = 0x80;
+/// simple extended content (security/segmentation)
+pub const CANXL_SEC: u8 = 0x01;
+/// 11 bit priority mask
+pub const CANXL_PRIO_MASK: u32 = SFF_MASK;
+/// bit offset of the VCID in the priority field
+pub const CANXL_VCID_OFFSET: u32 = 16;
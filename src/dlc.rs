@@ -0,0 +1,28 @@
+//! Conversion between the DLC field and payload lengths of CAN FD frames.
+//!
+//! Classic CAN frames carry their payload length in the DLC directly. CAN FD
+//! keeps the 4 bit DLC, but maps the values 9 to 15 to payloads of 12, 16,
+//! 20, 24, 32, 48 and 64 bytes.
+
+use constants::CANFD_MAX_DLEN;
+
+const DLC_TO_LEN: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 16, 20, 24, 32, 48, 64];
+
+/// Payload length encoded by a CAN FD DLC.
+///
+/// Only the lower 4 bits of `dlc` are used.
+pub fn fd_dlc_to_len(dlc: u8) -> u8 {
+    DLC_TO_LEN[(dlc & 0x0F) as usize]
+}
+
+/// Smallest DLC whose payload holds `len` bytes.
+///
+/// Lengths between the valid CAN FD payload sizes are rounded up, e.g. 10
+/// bytes need DLC 9 (12 bytes). Returns `None` for lengths above 64.
+pub fn fd_len_to_dlc(len: u8) -> Option<u8> {
+    if len as usize > CANFD_MAX_DLEN {
+        return None;
+    }
+
+    DLC_TO_LEN.iter().position(|&l| l >= len).map(|dlc| dlc as u8)
+}
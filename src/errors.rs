@@ -1,38 +1,74 @@
+//! Errors opening sockets, constructing frames and decoding error frames.
+
 use frame::CanFrame;
 
 // information from https://raw.githubusercontent.com/torvalds/linux/master/
 //                  /include/uapi/linux/can/error.h
 
-use std::convert::TryFrom;
-use std::{error, fmt};
+use core::convert::TryFrom;
+use core::fmt;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::error;
 
+#[cfg(feature = "std")]
 #[derive(Debug)]
 /// Errors opening socket
+///
+/// ```no_run
+/// use socketcan::errors::CanSocketOpenError;
+/// use socketcan::socket::CanSocket;
+///
+/// match CanSocket::open("can0") {
+///     Ok(socket) => (),
+///     Err(CanSocketOpenError::InterfaceNotFound(name)) => eprintln!("{} does not exist", name),
+///     Err(CanSocketOpenError::PermissionDenied(_)) => eprintln!("missing CAP_NET_RAW"),
+///     Err(e) => eprintln!("{}", e),
+/// }
+/// ```
 pub enum CanSocketOpenError {
-    /// Device could not be found
+    /// No interface with the given name exists
+    InterfaceNotFound(String),
+
+    /// System error while trying to look up device name
     LookupError(nix::Error),
 
+    /// Not allowed to open a raw CAN socket, which requires `CAP_NET_RAW`
+    PermissionDenied(std::io::Error),
+
     /// System error while trying to look up device name
     IOError(std::io::Error),
 }
 
+#[cfg(feature = "std")]
 impl fmt::Display for CanSocketOpenError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            CanSocketOpenError::LookupError(ref e) => write!(f, "CAN Device not found: {}", e),
+            CanSocketOpenError::InterfaceNotFound(ref name) => {
+                write!(f, "CAN interface {} does not exist", name)
+            }
+            CanSocketOpenError::LookupError(ref e) => write!(f, "CAN Device lookup failed: {}", e),
+            CanSocketOpenError::PermissionDenied(ref e) => {
+                write!(f,
+                       "permission denied opening raw CAN socket, CAP_NET_RAW or root is required: {}",
+                       e)
+            }
             CanSocketOpenError::IOError(ref e) => write!(f, "IO: {}", e),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for CanSocketOpenError {}
 
+#[cfg(feature = "std")]
 impl From<nix::Error> for CanSocketOpenError {
     fn from(e: nix::Error) -> CanSocketOpenError {
         CanSocketOpenError::LookupError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for CanSocketOpenError {
     fn from(e: std::io::Error) -> CanSocketOpenError {
         CanSocketOpenError::IOError(e)
@@ -42,28 +78,51 @@ impl From<std::io::Error> for CanSocketOpenError {
 #[derive(Debug, Copy, Clone)]
 /// Error that occurs when creating CAN packets
 pub enum ConstructionError {
-    /// CAN ID was outside the range of valid IDs
-    IDTooLarge,
-    /// More than 8 Bytes of payload data were passed in
-    TooMuchData,
+    /// CAN ID was outside the range of valid IDs, contains the ID
+    IDTooLarge(u32),
+    /// More payload data was passed in than the frame can hold (8 bytes for
+    /// classic CAN frames), contains the payload length
+    TooMuchData(usize),
+    /// Not enough payload data was passed in (CAN XL requires at least 1),
+    /// contains the payload length
+    TooLittleData(usize),
+    /// A buffer reinterpreted as a frame is not aligned as the frame type
+    /// requires, contains the buffer address
+    Misaligned(usize),
+    /// The frame flags form an invalid combination, contains the raw id
+    InvalidFlags(u32),
+    /// A filter list is longer than the kernel accepts
+    /// (`CAN_RAW_FILTER_MAX`), contains the number of filters
+    TooManyFilters(usize),
 }
 
 impl fmt::Display for ConstructionError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            ConstructionError::IDTooLarge => write!(f, "CAN ID too large"),
-            ConstructionError::TooMuchData => {
-                write!(f, "Payload is larger than CAN maximum of 8 bytes")
+            ConstructionError::IDTooLarge(id) => write!(f, "CAN ID 0x{:X} too large", id),
+            ConstructionError::TooMuchData(len) => {
+                write!(f, "Payload of {} bytes is larger than the CAN frame maximum", len)
+            }
+            ConstructionError::TooLittleData(len) => {
+                write!(f, "Payload of {} bytes is too short", len)
             }
+            ConstructionError::Misaligned(addr) => write!(f, "Buffer at 0x{:X} is misaligned", addr),
+            ConstructionError::InvalidFlags(id) => write!(f, "Invalid flag combination in CAN ID 0x{:X}", id),
+            ConstructionError::TooManyFilters(n) => write!(f, "{} filters exceed the kernel maximum", n),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for ConstructionError {
     fn description(&self) -> &str {
         match *self {
-            ConstructionError::IDTooLarge => "can id too large",
-            ConstructionError::TooMuchData => "too much data",
+            ConstructionError::IDTooLarge(_) => "can id too large",
+            ConstructionError::TooMuchData(_) => "too much data",
+            ConstructionError::TooLittleData(_) => "too little data",
+            ConstructionError::Misaligned(_) => "misaligned buffer",
+            ConstructionError::InvalidFlags(_) => "invalid flags",
+            ConstructionError::TooManyFilters(_) => "too many filters",
         }
     }
 }
@@ -120,9 +179,10 @@ impl fmt::Display for CanErrorDecodingFailure {
         })
     }
 }
+#[cfg(feature = "std")]
 impl error::Error for CanErrorDecodingFailure {}
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum CanError {
     /// TX timeout (by netdevice driver)
     TransmitTimeout,
@@ -160,6 +220,7 @@ pub enum CanError {
     Unknown(u32),
 }
 
+#[cfg(feature = "std")]
 impl error::Error for CanError {}
 
 impl fmt::Display for CanError {
@@ -180,7 +241,7 @@ impl fmt::Display for CanError {
 }
 
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ControllerProblem {
     // unspecified
     Unspecified,
@@ -207,6 +268,7 @@ pub enum ControllerProblem {
     Active,
 }
 
+#[cfg(feature = "std")]
 impl error::Error for ControllerProblem {}
 
 impl fmt::Display for ControllerProblem {
@@ -242,7 +304,22 @@ impl TryFrom<u8> for ControllerProblem {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+impl From<ControllerProblem> for u8 {
+    fn from(val: ControllerProblem) -> u8 {
+        match val {
+            ControllerProblem::Unspecified => 0x00,
+            ControllerProblem::ReceiveBufferOverflow => 0x01,
+            ControllerProblem::TransmitBufferOverflow => 0x02,
+            ControllerProblem::ReceiveErrorWarning => 0x04,
+            ControllerProblem::TransmitErrorWarning => 0x08,
+            ControllerProblem::ReceiveErrorPassive => 0x10,
+            ControllerProblem::TransmitErrorPassive => 0x20,
+            ControllerProblem::Active => 0x40,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ViolationType {
     /// Unspecified Violation
     Unspecified,
@@ -272,6 +349,7 @@ pub enum ViolationType {
     TransmissionError,
 }
 
+#[cfg(feature = "std")]
 impl error::Error for ViolationType {}
 
 impl fmt::Display for ViolationType {
@@ -310,10 +388,26 @@ impl TryFrom<u8> for ViolationType {
     }
 }
 
+impl From<ViolationType> for u8 {
+    fn from(val: ViolationType) -> u8 {
+        match val {
+            ViolationType::Unspecified => 0x00,
+            ViolationType::SingleBitError => 0x01,
+            ViolationType::FrameFormatError => 0x02,
+            ViolationType::BitStuffingError => 0x04,
+            ViolationType::UnableToSendDominantBit => 0x08,
+            ViolationType::UnableToSendRecessiveBit => 0x10,
+            ViolationType::BusOverload => 0x20,
+            ViolationType::Active => 0x40,
+            ViolationType::TransmissionError => 0x80,
+        }
+    }
+}
+
 /// Location
 ///
 /// Describes where inside a received frame an error occured.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Location {
     /// Unspecified
     Unspecified,
@@ -433,6 +527,33 @@ impl TryFrom<u8> for Location {
     }
 }
 
+impl From<Location> for u8 {
+    fn from(val: Location) -> u8 {
+        match val {
+            Location::Unspecified => 0x00,
+            Location::StartOfFrame => 0x03,
+            Location::Id2821 => 0x02,
+            Location::Id2018 => 0x06,
+            Location::SubstituteRtr => 0x04,
+            Location::IdentifierExtension => 0x05,
+            Location::Id1713 => 0x07,
+            Location::Id1205 => 0x0F,
+            Location::Id0400 => 0x0E,
+            Location::Rtr => 0x0C,
+            Location::Reserved1 => 0x0D,
+            Location::Reserved0 => 0x09,
+            Location::DataLengthCode => 0x0B,
+            Location::DataSection => 0x0A,
+            Location::CrcSequence => 0x08,
+            Location::CrcDelimiter => 0x18,
+            Location::AckSlot => 0x19,
+            Location::AckDelimiter => 0x1B,
+            Location::EndOfFrame => 0x1A,
+            Location::Intermission => 0x12,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum TransceiverError {
     Unspecified,
@@ -464,6 +585,7 @@ impl fmt::Display for TransceiverError {
     }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for TransceiverError {}
 
 impl TryFrom<u8> for TransceiverError {
@@ -486,14 +608,77 @@ impl TryFrom<u8> for TransceiverError {
     }
 }
 
-//TODO: can we convert this to the try_from pattern?
 impl CanError {
+    /// Decode an error frame with a single error class set.
+    ///
+    /// Use `all_from_frame` for frames reporting multiple conditions at once.
     pub fn from_frame(frame: &CanFrame) -> Result<CanError, CanErrorDecodingFailure> {
         if !frame.is_error() {
             return Err(CanErrorDecodingFailure::NotAnError);
         }
 
-        match frame.err() {
+        CanError::from_class(frame, frame.err())
+    }
+
+    /// Decode every error class set in an error frame.
+    ///
+    /// The errors are returned in order of their class bits. Classes not
+    /// known to this crate, such as the error counters (`CAN_ERR_CNT`), are
+    /// returned as `Unknown(class)`. Fails if the details of a known class
+    /// cannot be decoded.
+    pub fn all_from_frame(frame: &CanFrame) -> Result<Vec<CanError>, CanErrorDecodingFailure> {
+        if !frame.is_error() {
+            return Err(CanErrorDecodingFailure::NotAnError);
+        }
+
+        let err = frame.err();
+        (0..32)
+            .map(|bit| 1 << bit)
+            .filter(|class| err & class != 0)
+            .map(|class| match CanError::from_class(frame, class) {
+                Err(CanErrorDecodingFailure::UnknownErrorType(class)) => Ok(CanError::Unknown(class)),
+                r => r,
+            })
+            .collect()
+    }
+
+    /// Encode the error as an error frame, the inverse of `from_frame`.
+    ///
+    /// The frame carries the error class in its id and the details in the
+    /// data bytes, laid out as in `linux/can/error.h`. `Unknown(class)` is
+    /// encoded with `class` as its error class; this fails if `class` does
+    /// not fit into the id.
+    pub fn to_frame(self) -> Result<CanFrame, ConstructionError> {
+        let mut data = [0; 8];
+
+        let class = match self {
+            CanError::TransmitTimeout => 0x00000001,
+            CanError::LostArbitration(n) => {
+                data[0] = n;
+                0x00000002
+            }
+            CanError::ControllerProblem(problem) => {
+                data[1] = problem.into();
+                0x00000004
+            }
+            CanError::ProtocolViolation { vtype, location } => {
+                data[2] = vtype.into();
+                data[3] = location.into();
+                0x00000008
+            }
+            CanError::TransceiverError => 0x00000010,
+            CanError::NoAck => 0x00000020,
+            CanError::BusOff => 0x00000040,
+            CanError::BusError => 0x00000080,
+            CanError::Restarted => 0x00000100,
+            CanError::Unknown(class) => class,
+        };
+
+        CanFrame::new(class, &data, false, true)
+    }
+
+    fn from_class(frame: &CanFrame, class: u32) -> Result<CanError, CanErrorDecodingFailure> {
+        match class {
             0x00000001 => Ok(CanError::TransmitTimeout),
             0x00000002 => Ok(CanError::LostArbitration(get_data(frame, 0)?)),
             0x00000004 => {
@@ -517,16 +702,36 @@ impl CanError {
     }
 }
 
+impl<'a> TryFrom<&'a CanFrame> for CanError {
+    type Error = CanErrorDecodingFailure;
+
+    fn try_from(frame: &'a CanFrame) -> Result<Self, Self::Error> {
+        CanError::from_frame(frame)
+    }
+}
+
+impl TryFrom<CanFrame> for CanError {
+    type Error = CanErrorDecodingFailure;
+
+    fn try_from(frame: CanFrame) -> Result<Self, Self::Error> {
+        CanError::from_frame(&frame)
+    }
+}
+
 pub trait ControllerSpecificErrorInformation {
     fn get_ctrl_err(&self) -> Option<&[u8]>;
 }
 
 impl ControllerSpecificErrorInformation for CanFrame {
+    /// Controller specific bytes 5 to 7 of a controller problem error frame.
+    ///
+    /// Returns `None` for data frames and error frames of other classes.
     #[inline]
     fn get_ctrl_err(&self) -> Option<&[u8]> {
         let data = self.data();
 
-        if data.len() != 8 {
+        // only controller problems (CAN_ERR_CRTL) carry controller specific data
+        if !self.is_error() || self.err() & 0x00000004 == 0 || data.len() != 8 {
             None
         } else {
             Some(&data[5..])
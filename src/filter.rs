@@ -1,4 +1,9 @@
 use errors::ConstructionError;
+use constants::*;
+use frame::CanFrame;
+use alloc::vec::Vec;
+use core::cmp;
+use core::ops::RangeInclusive;
 
 /// CanFilter
 ///
@@ -11,6 +16,9 @@ pub struct CanFilter {
     _mask: u32,
 }
 
+// must match the kernel's struct can_filter
+const _: () = assert!(core::mem::size_of::<CanFilter>() == 8);
+
 impl CanFilter {
     /// Construct a new CAN filter.
     pub fn new(id: u32, mask: u32) -> Result<CanFilter, ConstructionError> {
@@ -19,4 +27,280 @@ impl CanFilter {
                _mask: mask,
            })
     }
+
+    /// Construct a filter for extended frames.
+    ///
+    /// Sets the EFF flag in both id and mask, so only extended frames match,
+    /// even if `id` would fit into 11 bits.
+    pub fn new_extended(id: u32, mask: u32) -> Result<CanFilter, ConstructionError> {
+        if id > EFF_MASK {
+            return Err(ConstructionError::IDTooLarge(id));
+        }
+
+        Ok(CanFilter {
+               _id: id | EFF_FLAG,
+               _mask: mask | EFF_FLAG,
+           })
+    }
+
+    /// Construct filters matching exactly the ids `start..=end`.
+    ///
+    /// Decomposes the range into the minimal number of aligned blocks, each
+    /// expressed as a single id/mask filter. Ranges up to `SFF_MASK` match
+    /// standard frames only, larger ranges match extended frames only. An
+    /// empty range (`start > end`) results in no filters.
+    pub fn from_range(start: u32, end: u32) -> Result<Vec<CanFilter>, ConstructionError> {
+        CanFilter::range(start, end, end > SFF_MASK)
+    }
+
+    /// Filters for `start..=end` matching either extended or standard frames.
+    fn range(start: u32, end: u32, extended: bool) -> Result<Vec<CanFilter>, ConstructionError> {
+        if end > EFF_MASK {
+            return Err(ConstructionError::IDTooLarge(end));
+        }
+
+        let (id_mask, flag) = if extended {
+            (EFF_MASK, EFF_FLAG)
+        } else {
+            (SFF_MASK, 0)
+        };
+        let width = id_mask.count_ones();
+
+        let mut filters = Vec::new();
+        let mut lo = u64::from(start);
+        let hi = u64::from(end);
+
+        while lo <= hi {
+            // largest aligned block starting at lo that does not exceed hi
+            let mut size = 1u64 << cmp::min(lo.trailing_zeros(), width);
+            while lo + size - 1 > hi {
+                size >>= 1;
+            }
+
+            filters.push(CanFilter {
+                _id: lo as u32 | flag,
+                _mask: !(size as u32 - 1) & id_mask | EFF_FLAG,
+            });
+            lo += size;
+        }
+
+        Ok(filters)
+    }
+
+    /// Check whether a frame passes the filter.
+    ///
+    /// Follows the matching rules of the kernel, allowing the same filters
+    /// to be used for additional filtering in userspace. This includes the
+    /// `INV_FILTER` flag and the frame type bits. Error frames only match
+    /// filters with `ERR_FLAG` set in the mask and at least one error class
+    /// in common.
+    pub fn matches(&self, frame: &CanFrame) -> bool {
+        if self._mask & ERR_FLAG != 0 {
+            return frame.is_error() && frame.err() & self._mask & ERR_MASK != 0;
+        }
+
+        if frame.is_error() {
+            return false;
+        }
+
+        let mask = self.effective_mask();
+        let matched = frame.raw_id() & mask == self._id & mask;
+
+        if self._id & INV_FILTER != 0 {
+            !matched
+        } else {
+            matched
+        }
+    }
+
+    /// Mask applied to the id of data and remote frames.
+    fn effective_mask(&self) -> u32 {
+        let mut mask = self._mask & (EFF_FLAG | RTR_FLAG | EFF_MASK);
+
+        // filtering for standard frames only, the extended id bits are unused
+        if mask & EFF_FLAG != 0 && self._id & EFF_FLAG == 0 {
+            mask &= SFF_MASK | EFF_FLAG | RTR_FLAG;
+        }
+
+        mask
+    }
+
+    /// Check whether every frame matching `other` also matches this filter.
+    ///
+    /// Only decided for plain filters, inverted and error filters never
+    /// cover or are covered by another filter.
+    fn covers(&self, other: &CanFilter) -> bool {
+        let special = INV_FILTER | ERR_FLAG;
+        if self._id & INV_FILTER != 0 || other._id & INV_FILTER != 0 ||
+           (self._mask | other._mask) & special != 0 {
+            return false;
+        }
+
+        let mask = self.effective_mask();
+        mask & !other.effective_mask() == 0 && self._id & mask == other._id & mask
+    }
+
+    /// Remove filters that are redundant because another filter matches a
+    /// superset of their frames.
+    ///
+    /// The resulting list matches exactly the same frames as `filters`, but
+    /// is usually shorter. Of several equivalent filters, the first one is
+    /// kept. The order of the remaining filters is preserved.
+    ///
+    /// This only holds for the default semantics of a frame having to match
+    /// any filter. With `CanSocket::set_join_filters(true)` a frame has to
+    /// match all filters, and dropping a filter lets more frames through.
+    pub fn optimize(filters: &[CanFilter]) -> Vec<CanFilter> {
+        filters.iter()
+            .enumerate()
+            .filter(|&(i, f)| {
+                !filters.iter().enumerate().any(|(j, other)| {
+                    j != i && other.covers(f) && (j < i || !f.covers(other))
+                })
+            })
+            .map(|(_, f)| *f)
+            .collect()
+    }
+
+    /// Restrict the filter to data frames.
+    ///
+    /// The frame type is encoded as the `RTR_FLAG` bit of the id, this adds
+    /// it to the mask and clears it in the filter id. A frame then has to
+    /// match both the id bits and the frame type to pass.
+    pub fn data_only(self) -> CanFilter {
+        CanFilter {
+            _id: self._id & !RTR_FLAG,
+            _mask: self._mask | RTR_FLAG,
+        }
+    }
+
+    /// Restrict the filter to remote transmission requests.
+    ///
+    /// Counterpart to `data_only`, sets the `RTR_FLAG` bit in both filter id
+    /// and mask.
+    pub fn rtr_only(self) -> CanFilter {
+        CanFilter {
+            _id: self._id | RTR_FLAG,
+            _mask: self._mask | RTR_FLAG,
+        }
+    }
+}
+
+/// The default filter has id and mask 0, matching all frames.
+impl Default for CanFilter {
+    fn default() -> CanFilter {
+        CanFilter {
+            _id: 0,
+            _mask: 0,
+        }
+    }
+}
+
+/// FilterSet
+///
+/// Builds the kernel filter list from accepted id ranges and rejected ids.
+/// A frame passes if it is within one of the accepted ranges and its id is
+/// not rejected. Without any accepted range, all frames except the rejected
+/// ones pass.
+///
+/// As with `CanFilter::from_range`, ranges up to `SFF_MASK` match standard
+/// frames, larger ones extended frames. A rejected id is removed from every
+/// accepted range containing it.
+///
+/// ```
+/// use socketcan::filter::FilterSet;
+///
+/// let filters = FilterSet::new()
+///     .accept(0x100..=0x1FF)
+///     .accept_exact(0x7E8)
+///     .reject(0x180)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FilterSet {
+    accepted: Vec<(u32, u32)>,
+    rejected: Vec<u32>,
+}
+
+impl FilterSet {
+    /// Construct an empty set, passing all frames.
+    pub fn new() -> FilterSet {
+        FilterSet::default()
+    }
+
+    /// Accept the ids in `range`.
+    pub fn accept(mut self, range: RangeInclusive<u32>) -> FilterSet {
+        self.accepted.push((*range.start(), *range.end()));
+        self
+    }
+
+    /// Accept a single id.
+    pub fn accept_exact(self, id: u32) -> FilterSet {
+        self.accept(id..=id)
+    }
+
+    /// Reject a single id, even if it is within an accepted range.
+    pub fn reject(mut self, id: u32) -> FilterSet {
+        self.rejected.push(id);
+        self
+    }
+
+    /// Build the filter list, as passed to `CanSocket::set_filters`.
+    ///
+    /// Fails if an id exceeds `EFF_MASK`, or with `TooManyFilters` if the
+    /// list is longer than the kernel accepts (`CAN_RAW_FILTER_MAX`, 512).
+    /// Every id rejected from within a range splits it into up to 2 × 29
+    /// aligned blocks, so a handful of rejects without any accepted range
+    /// can already exceed the limit.
+    pub fn build(&self) -> Result<Vec<CanFilter>, ConstructionError> {
+        if let Some(&id) = self.rejected.iter().find(|&&id| id > EFF_MASK) {
+            return Err(ConstructionError::IDTooLarge(id));
+        }
+
+        let mut rejected = self.rejected.clone();
+        rejected.sort_unstable();
+        rejected.dedup();
+
+        let accepted = if self.accepted.is_empty() {
+            match rejected.len() {
+                0 => return Ok(vec![CanFilter::default()]),
+                // a single inverted filter rejects the id in both frame formats
+                1 => {
+                    return Ok(vec![CanFilter {
+                        _id: rejected[0] | INV_FILTER,
+                        _mask: EFF_MASK,
+                    }])
+                }
+                _ => vec![(0, SFF_MASK), (0, EFF_MASK)],
+            }
+        } else {
+            self.accepted.clone()
+        };
+
+        let mut filters = Vec::new();
+        for (start, end) in accepted {
+            // pieces keep the frame format of the whole range
+            let extended = end > SFF_MASK;
+            let mut lo = start;
+
+            for &id in rejected.iter().filter(|&&id| id >= start && id <= end) {
+                if id > lo {
+                    filters.extend(CanFilter::range(lo, id - 1, extended)?);
+                }
+                lo = id + 1;
+            }
+
+            if lo <= end {
+                filters.extend(CanFilter::range(lo, end, extended)?);
+            }
+        }
+
+        let filters = CanFilter::optimize(&filters);
+        if filters.len() > CAN_RAW_FILTER_MAX {
+            return Err(ConstructionError::TooManyFilters(filters.len()));
+        }
+
+        Ok(filters)
+    }
 }
@@ -1,7 +1,13 @@
-use std::fmt;
+use core::{cmp, fmt};
+use core::convert::TryFrom;
+use core::ops::{self, Range};
+#[cfg(feature = "std")]
+use core::mem;
+use alloc::string::String;
+use alloc::vec::Vec;
 use errors::{ConstructionError, CanError, CanErrorDecodingFailure};
 use constants::*;
-use itertools::Itertools;
+use dlc::{fd_dlc_to_len, fd_len_to_dlc};
 
 /// CanFrame
 ///
@@ -24,20 +30,34 @@ pub struct CanFrame {
     _data: [u8; 8],
 }
 
+// reads and writes transfer size_of::<CanFrame>() bytes, which must match
+// the kernel's struct can_frame
+const _: () = assert!(core::mem::size_of::<CanFrame>() == CAN_MTU);
+
 impl CanFrame {
+    /// Construct a new CAN frame.
+    ///
+    /// The `err` flag marks the frame as an error frame. Error frames are
+    /// generated by the kernel and only ever received; they cannot be
+    /// transmitted, and `CanSocket::write` will refuse to send them. Outgoing
+    /// frames should always pass `false`.
+    ///
+    /// For error frames `id` holds the error class as defined in
+    /// `linux/can/error.h` rather than an arbitration id, so it is never
+    /// promoted to an extended frame.
     pub fn new(id: u32, data: &[u8], rtr: bool, err: bool) -> Result<CanFrame, ConstructionError> {
         let mut _id = id;
 
         if data.len() > 8 {
-            return Err(ConstructionError::TooMuchData);
+            return Err(ConstructionError::TooMuchData(data.len()));
         }
 
         if id > EFF_MASK {
-            return Err(ConstructionError::IDTooLarge);
+            return Err(ConstructionError::IDTooLarge(id));
         }
 
-        // set EFF_FLAG on large message
-        if id > SFF_MASK {
+        // set EFF_FLAG on large message, error classes are not ids
+        if id > SFF_MASK && !err {
             _id |= EFF_FLAG;
         }
 
@@ -50,14 +70,84 @@ impl CanFrame {
         }
 
         let mut full_data = [0; 8];
+        full_data[..data.len()].copy_from_slice(data);
 
-        // not cool =/
-        for (n, c) in data.iter().enumerate() {
-            full_data[n] = *c;
+        Ok(CanFrame {
+               _id: _id,
+               _data_len: data.len() as u8,
+               _pad: 0,
+               _res0: 0,
+               _res1: 0,
+               _data: full_data,
+           })
+    }
+
+    /// Construct a new CAN frame with the given flags.
+    ///
+    /// Like `new`, ids larger than 11 bits result in an extended frame
+    /// unless `FrameFlags::ERR` is set. `FrameFlags::EFF` forces an extended
+    /// frame for small ids.
+    pub fn with_flags(id: u32, data: &[u8], flags: FrameFlags) -> Result<CanFrame, ConstructionError> {
+        if id > EFF_MASK {
+            return Err(ConstructionError::IDTooLarge(id));
         }
 
+        let mut flags = flags;
+        if id > SFF_MASK && !flags.contains(FrameFlags::ERR) {
+            flags |= FrameFlags::EFF;
+        }
+
+        CanFrame::with_raw_id(id | flags.bits(), data)
+    }
+
+    /// Construct a new standard (11 bit id) data frame.
+    ///
+    /// Unlike `new`, ids above `SFF_MASK` are rejected instead of being
+    /// promoted to an extended frame.
+    pub fn new_standard(id: u32, data: &[u8]) -> Result<CanFrame, ConstructionError> {
+        if id > SFF_MASK {
+            return Err(ConstructionError::IDTooLarge(id));
+        }
+
+        CanFrame::with_raw_id(id, data)
+    }
+
+    /// Construct a new extended (29 bit id) data frame.
+    ///
+    /// The EFF flag is always set, so small ids such as `0x001` still produce
+    /// an extended frame.
+    pub fn new_extended(id: u32, data: &[u8]) -> Result<CanFrame, ConstructionError> {
+        if id > EFF_MASK {
+            return Err(ConstructionError::IDTooLarge(id));
+        }
+
+        CanFrame::with_raw_id(id | EFF_FLAG, data)
+    }
+
+    /// Construct the data frame answering a remote transmission request.
+    ///
+    /// Uses the id of `request`, keeping its standard or extended format,
+    /// with the RTR flag cleared. The requested length is found in
+    /// `request.data().len()`, but is not enforced.
+    pub fn respond_to_rtr(request: &CanFrame, data: &[u8]) -> Result<CanFrame, ConstructionError> {
+        if request.is_extended() {
+            CanFrame::new_extended(request.id(), data)
+        } else {
+            CanFrame::new_standard(request.id(), data)
+        }
+    }
+
+    /// Construct a frame from an id word that already contains all flags.
+    fn with_raw_id(_id: u32, data: &[u8]) -> Result<CanFrame, ConstructionError> {
+        if data.len() > 8 {
+            return Err(ConstructionError::TooMuchData(data.len()));
+        }
+
+        let mut full_data = [0; 8];
+        full_data[..data.len()].copy_from_slice(data);
+
         Ok(CanFrame {
-               _id: _id,
+               _id,
                _data_len: data.len() as u8,
                _pad: 0,
                _res0: 0,
@@ -66,6 +156,85 @@ impl CanFrame {
            })
     }
 
+    /// Construct a new data frame from a full 8 byte buffer.
+    ///
+    /// Takes the data buffer by value, avoiding the copy done by `new`. Only
+    /// the first `len` bytes are considered payload, the rest is zeroed.
+    pub fn from_array(id: u32, data: [u8; 8], len: u8) -> Result<CanFrame, ConstructionError> {
+        let mut _id = id;
+        let mut data = data;
+
+        if len > 8 {
+            return Err(ConstructionError::TooMuchData(len as usize));
+        }
+
+        if id > EFF_MASK {
+            return Err(ConstructionError::IDTooLarge(id));
+        }
+
+        // set EFF_FLAG on large message
+        if id > SFF_MASK {
+            _id |= EFF_FLAG;
+        }
+
+        data[len as usize..].fill(0);
+
+        Ok(CanFrame {
+               _id,
+               _data_len: len,
+               _pad: 0,
+               _res0: 0,
+               _res1: 0,
+               _data: data,
+           })
+    }
+
+    /// Construct a new data frame from an iterator of payload bytes.
+    ///
+    /// Collects the bytes without an intermediate allocation. Fails with
+    /// `TooMuchData` if the iterator yields more than 8 bytes, the remaining
+    /// bytes are consumed to report the full length.
+    pub fn from_iter<I>(id: u32, data: I) -> Result<CanFrame, ConstructionError>
+        where I: IntoIterator<Item = u8>
+    {
+        let mut buf = [0; 8];
+        let mut len = 0;
+        let mut data = data.into_iter();
+
+        while let Some(byte) = data.next() {
+            if len == buf.len() {
+                return Err(ConstructionError::TooMuchData(len + 1 + data.count()));
+            }
+            buf[len] = byte;
+            len += 1;
+        }
+
+        CanFrame::from_array(id, buf, len as u8)
+    }
+
+    /// Construct a new data frame at compile time.
+    ///
+    /// Unlike `from_array`, the inputs are not validated, allowing frames to
+    /// be built in a `const` or `static`. The caller must ensure that `id`
+    /// fits into 29 bits and `len` is at most 8. Bytes after `len` are zeroed.
+    pub const fn const_new(id: u32, data: [u8; 8], len: u8) -> CanFrame {
+        let mut data = data;
+        let mut i = len as usize;
+        while i < data.len() {
+            data[i] = 0;
+            i += 1;
+        }
+
+        CanFrame {
+            _id: if id > SFF_MASK { id | EFF_FLAG } else { id },
+            _data_len: len,
+            _pad: 0,
+            _res0: 0,
+            _res1: 0,
+            _data: data,
+        }
+    }
+
     pub fn empty() -> CanFrame {
         CanFrame::new(0, &[0; 8], false, false).unwrap()
     }
@@ -80,18 +249,40 @@ impl CanFrame {
         }
     }
 
+    /// Return the raw 32 bit id word, including EFF/RTR/ERR flags
+    ///
+    /// This is the id exactly as it is exchanged with the kernel. Since it
+    /// includes the flag bits, it must not be compared against an
+    /// application CAN ID; use `id()` for that.
+    #[inline]
+    pub fn raw_id(&self) -> u32 {
+        self._id
+    }
+
     /// Return the error message
     #[inline]
     pub fn err(&self) -> u32 {
         self._id & ERR_MASK
     }
 
+    /// The EFF/RTR/ERR flags of the frame
+    #[inline]
+    pub fn flags(&self) -> FrameFlags {
+        FrameFlags::from_bits_truncate(self._id)
+    }
+
     /// Check if frame uses 29 bit extended frame format
     #[inline]
     pub fn is_extended(&self) -> bool {
         self._id & EFF_FLAG != 0
     }
 
+    /// Check if frame uses 11 bit standard frame format
+    #[inline]
+    pub fn is_standard(&self) -> bool {
+        !self.is_extended()
+    }
+
     /// Check if frame is an error message
     #[inline]
     pub fn is_error(&self) -> bool {
@@ -104,10 +295,107 @@ impl CanFrame {
         self._id & RTR_FLAG != 0
     }
 
+    /// Check if frame is a data frame (neither RTR nor error message)
+    #[inline]
+    pub fn is_data_frame(&self) -> bool {
+        !self.is_rtr() && !self.is_error()
+    }
+
+    /// Check the frame for consistency.
+    ///
+    /// Frames built by the constructors are always valid, but frames read
+    /// from untrusted bytes may not be. Fails if the data length exceeds 8,
+    /// if a standard frame has id bits beyond `SFF_MASK` set or if an error
+    /// frame is also marked as extended or remote frame.
+    pub fn validate(&self) -> Result<(), ConstructionError> {
+        if self._data_len > 8 {
+            return Err(ConstructionError::TooMuchData(self._data_len as usize));
+        }
+
+        if self.is_error() {
+            if self._id & (EFF_FLAG | RTR_FLAG) != 0 {
+                return Err(ConstructionError::InvalidFlags(self._id));
+            }
+        } else if self.is_standard() && self._id & EFF_MASK > SFF_MASK {
+            return Err(ConstructionError::IDTooLarge(self._id & EFF_MASK));
+        }
+
+        Ok(())
+    }
+
     /// A slice into the actual data. Slice will always be <= 8 bytes in length
+    ///
+    /// A corrupt data length above 8 is clamped, see `validate`.
     #[inline]
     pub fn data(&self) -> &[u8] {
-        &self._data[..(self._data_len as usize)]
+        &self._data[..(self._data_len as usize).min(self._data.len())]
+    }
+
+    /// Payload bytes `offset..offset + width`, if within the data length.
+    fn payload_range(&self, offset: usize, width: usize) -> Option<&[u8]> {
+        let end = offset.checked_add(width)?;
+        self.data().get(offset..end)
+    }
+
+    /// Read the byte at `offset` of the payload.
+    ///
+    /// Returns `None` if `offset` lies beyond the data length.
+    #[inline]
+    pub fn get_u8(&self, offset: usize) -> Option<u8> {
+        self.data().get(offset).cloned()
+    }
+
+    /// Read a little endian `u16` starting at `offset` of the payload.
+    ///
+    /// Returns `None` if the value extends beyond the data length.
+    pub fn get_u16_le(&self, offset: usize) -> Option<u16> {
+        self.payload_range(offset, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    /// Read a big endian `u16` starting at `offset` of the payload.
+    ///
+    /// Returns `None` if the value extends beyond the data length.
+    pub fn get_u16_be(&self, offset: usize) -> Option<u16> {
+        self.payload_range(offset, 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    /// Read a little endian `u32` starting at `offset` of the payload.
+    ///
+    /// Returns `None` if the value extends beyond the data length.
+    pub fn get_u32_le(&self, offset: usize) -> Option<u32> {
+        self.payload_range(offset, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Read a big endian `u32` starting at `offset` of the payload.
+    ///
+    /// Returns `None` if the value extends beyond the data length.
+    pub fn get_u32_be(&self, offset: usize) -> Option<u32> {
+        self.payload_range(offset, 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// XOR of the payload bytes in `range`.
+    ///
+    /// Panics if `range` extends beyond the data length, like slicing
+    /// `data()` does.
+    pub fn checksum_xor(&self, range: Range<usize>) -> u8 {
+        self.data()[range].iter().fold(0, |acc, b| acc ^ b)
+    }
+
+    /// CRC8 of the payload bytes in `range`.
+    ///
+    /// Uses the SAE J1850 parameters common in automotive protocols (e.g.
+    /// AUTOSAR E2E profile 1): polynomial 0x1D, initial value and final XOR
+    /// 0xFF. Panics if `range` extends beyond the data length.
+    pub fn checksum_crc8(&self, range: Range<usize>) -> u8 {
+        let crc = self.data()[range].iter().fold(0xFF, |mut crc: u8, b| {
+            crc ^= b;
+            for _ in 0..8 {
+                crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x1D } else { crc << 1 };
+            }
+            crc
+        });
+
+        crc ^ 0xFF
     }
 
     /// Read error from message and transform it into a `CanError`.
@@ -122,15 +410,766 @@ impl CanFrame {
     pub fn error(&self) -> Result<CanError, CanErrorDecodingFailure> {
         CanError::from_frame(self)
     }
+
+    /// The frame as the 16 bytes of the kernel's `struct can_frame`.
+    ///
+    /// The id word is stored in native byte order. Use `CanFrame::try_from`
+    /// to convert the bytes back into a frame.
+    pub fn as_bytes(&self) -> &[u8] {
+        // CanFrame has no padding, all CAN_MTU bytes are initialized
+        unsafe { core::slice::from_raw_parts(self as *const CanFrame as *const u8, CAN_MTU) }
+    }
+
+    /// Reinterpret a received buffer as a frame, without copying.
+    ///
+    /// `buf` must hold exactly `CAN_MTU` bytes in the layout of
+    /// `struct can_frame` and be aligned to 4 bytes, which holds for each
+    /// slot of a buffer of frames filled by `recvmmsg`, for example. As with
+    /// `TryFrom<&[u8]>`, a data length above 8 is rejected.
+    pub fn from_bytes_ref(buf: &[u8]) -> Result<&CanFrame, ConstructionError> {
+        if buf.len() < CAN_MTU {
+            return Err(ConstructionError::TooLittleData(buf.len()));
+        }
+        if buf.len() > CAN_MTU {
+            return Err(ConstructionError::TooMuchData(buf.len()));
+        }
+        if buf.as_ptr() as usize % core::mem::align_of::<CanFrame>() != 0 {
+            return Err(ConstructionError::Misaligned(buf.as_ptr() as usize));
+        }
+        if buf[4] > 8 {
+            return Err(ConstructionError::TooMuchData(buf[4] as usize));
+        }
+
+        // size and alignment are checked, and every bit pattern is a valid
+        // CanFrame, which consists of integers only
+        Ok(unsafe { &*(buf.as_ptr() as *const CanFrame) })
+    }
+
+    /// Key comparing frames by bus arbitration, lower keys win.
+    ///
+    /// Lays out the bits of the arbitration field in the order they are sent
+    /// on the bus: the 11 bit base id, then RTR and IDE for standard frames
+    /// or SRR, IDE, the 18 bit id extension and RTR for extended frames.
+    /// Dominant bits are 0, so a lower key means a higher priority.
+    fn arbitration_key(&self) -> u32 {
+        let rtr = self.is_rtr() as u32;
+
+        if self.is_extended() {
+            let id = self.id();
+            (id >> 18) << 21 | 1 << 20 | 1 << 19 | (id & 0x3FFFF) << 1 | rtr
+        } else {
+            self.id() << 21 | rtr << 20
+        }
+    }
+
+    /// Human readable description of the frame, including decoded flags.
+    ///
+    /// Renders e.g. `EFF RTR id=0x18DAF110 dlc=0 []` or
+    /// `SFF id=0x123 dlc=2 [DE AD]`, useful for logging.
+    pub fn describe(&self) -> String {
+        let mut flags = vec![if self.is_extended() { "EFF" } else { "SFF" }];
+
+        if self.is_rtr() {
+            flags.push("RTR");
+        }
+
+        if self.is_error() {
+            flags.push("ERR");
+        }
+
+        let data: Vec<String> = self.data().iter().map(|v| format!("{:02X}", v)).collect();
+
+        format!("{} id=0x{:X} dlc={} [{}]",
+                flags.join(" "),
+                self.id(),
+                self._data_len,
+                data.join(" "))
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<libc::can_frame> for CanFrame {
+    fn from(frame: libc::can_frame) -> CanFrame {
+        CanFrame {
+            _id: frame.can_id,
+            _data_len: frame.can_dlc,
+            _pad: 0,
+            _res0: 0,
+            _res1: frame.len8_dlc,
+            _data: frame.data,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<CanFrame> for libc::can_frame {
+    fn from(frame: CanFrame) -> libc::can_frame {
+        // padding fields are private in libc, start out zeroed
+        let mut raw: libc::can_frame = unsafe { mem::zeroed() };
+        raw.can_id = frame._id;
+        raw.can_dlc = frame._data_len;
+        raw.len8_dlc = frame._res1;
+        raw.data = frame._data;
+        raw
+    }
+}
+
+/// Frames are equal if id, flags and payload match; padding and reserved
+/// bytes are ignored. Remote transmission requests carry no payload, so only
+/// their DLC (the requested length) is compared.
+impl PartialEq for CanFrame {
+    fn eq(&self, other: &CanFrame) -> bool {
+        if self._id != other._id {
+            return false;
+        }
+
+        if self.is_rtr() {
+            self._data_len == other._data_len
+        } else {
+            self.data() == other.data()
+        }
+    }
+}
+
+impl Eq for CanFrame {}
+
+impl Default for CanFrame {
+    fn default() -> CanFrame {
+        CanFrame::empty()
+    }
+}
+
+/// Frames are ordered by bus priority: a frame is *greater* than another if
+/// it would win arbitration against it on the bus, i.e. usually if it has the
+/// lower id. A `BinaryHeap<CanFrame>` thus yields the highest priority frame
+/// first.
+///
+/// Standard frames win against extended frames with the same base id, and
+/// data frames against remote transmission requests. Frames with identical
+/// arbitration fields are ordered by their remaining content to stay
+/// consistent with `Eq`.
+impl Ord for CanFrame {
+    fn cmp(&self, other: &CanFrame) -> cmp::Ordering {
+        other.arbitration_key()
+            .cmp(&self.arbitration_key())
+            .then_with(|| self._id.cmp(&other._id))
+            .then_with(|| {
+                // consistent with PartialEq, identical ids are either both RTR or neither
+                if self.is_rtr() {
+                    self._data_len.cmp(&other._data_len)
+                } else {
+                    self.data().cmp(other.data())
+                }
+            })
+    }
+}
+
+impl PartialOrd for CanFrame {
+    fn partial_cmp(&self, other: &CanFrame) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl fmt::UpperHex for CanFrame {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f, "{:X}#", self.id())?;
 
-        let mut parts = self.data().iter().map(|v| format!("{:02X}", v));
-
         let sep = if f.alternate() { " " } else { "" };
-        write!(f, "{}", parts.join(sep))
+
+        for (n, v) in self.data().iter().enumerate() {
+            if n > 0 {
+                f.write_str(sep)?;
+            }
+            write!(f, "{:02X}", v)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builder composing a `CanFrame` payload from integer signals.
+///
+/// Values are written at byte offsets, the data length of the resulting frame
+/// is the end of the furthest write. Writes extending beyond 8 bytes are
+/// reported by `build`.
+///
+/// ```
+/// use socketcan::frame::FrameBuilder;
+///
+/// let frame = FrameBuilder::new()
+///     .put_u16_le(0, 1500)
+///     .put_u16_le(2, 200)
+///     .build(0x123)
+///     .unwrap();
+/// assert_eq!(frame.data(), &[0xDC, 0x05, 0xC8, 0x00]);
+/// ```
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FrameBuilder {
+    data: [u8; 8],
+    len: usize,
+    overflow: Option<usize>,
+}
+
+impl FrameBuilder {
+    pub fn new() -> FrameBuilder {
+        FrameBuilder::default()
+    }
+
+    fn put(mut self, offset: usize, bytes: &[u8]) -> FrameBuilder {
+        match offset.checked_add(bytes.len()) {
+            Some(end) if end <= self.data.len() => {
+                self.data[offset..end].copy_from_slice(bytes);
+                self.len = cmp::max(self.len, end);
+            }
+            end => {
+                let end = end.unwrap_or(usize::MAX);
+                self.overflow = Some(cmp::max(self.overflow.unwrap_or(0), end));
+            }
+        }
+        self
+    }
+
+    /// Write a byte at `offset`.
+    pub fn put_u8(self, offset: usize, value: u8) -> FrameBuilder {
+        self.put(offset, &[value])
+    }
+
+    /// Write a little endian `u16` starting at `offset`.
+    pub fn put_u16_le(self, offset: usize, value: u16) -> FrameBuilder {
+        self.put(offset, &value.to_le_bytes())
+    }
+
+    /// Write a big endian `u16` starting at `offset`.
+    pub fn put_u16_be(self, offset: usize, value: u16) -> FrameBuilder {
+        self.put(offset, &value.to_be_bytes())
+    }
+
+    /// Write a little endian `u32` starting at `offset`.
+    pub fn put_u32_le(self, offset: usize, value: u32) -> FrameBuilder {
+        self.put(offset, &value.to_le_bytes())
+    }
+
+    /// Write a big endian `u32` starting at `offset`.
+    pub fn put_u32_be(self, offset: usize, value: u32) -> FrameBuilder {
+        self.put(offset, &value.to_be_bytes())
+    }
+
+    /// Build a data frame with the given id.
+    ///
+    /// Fails with `TooMuchData` if any write extended beyond 8 bytes.
+    pub fn build(&self, id: u32) -> Result<CanFrame, ConstructionError> {
+        if let Some(end) = self.overflow {
+            return Err(ConstructionError::TooMuchData(end));
+        }
+
+        CanFrame::new(id, &self.data[..self.len], false, false)
+    }
+}
+
+/// Parse the 16 byte layout produced by `CanFrame::as_bytes`.
+///
+/// Fails with `TooLittleData` or `TooMuchData` if the slice is not exactly
+/// 16 bytes long, or with `TooMuchData` if it encodes more than 8 bytes of
+/// payload.
+impl<'a> TryFrom<&'a [u8]> for CanFrame {
+    type Error = ConstructionError;
+
+    fn try_from(bytes: &'a [u8]) -> Result<CanFrame, ConstructionError> {
+        if bytes.len() < CAN_MTU {
+            return Err(ConstructionError::TooLittleData(bytes.len()));
+        }
+        if bytes.len() > CAN_MTU {
+            return Err(ConstructionError::TooMuchData(bytes.len()));
+        }
+        if bytes[4] > 8 {
+            return Err(ConstructionError::TooMuchData(bytes[4] as usize));
+        }
+
+        let mut data = [0; 8];
+        data.copy_from_slice(&bytes[8..]);
+
+        Ok(CanFrame {
+            _id: u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            _data_len: bytes[4],
+            _pad: bytes[5],
+            _res0: bytes[6],
+            _res1: bytes[7],
+            _data: data,
+        })
+    }
+}
+
+/// FrameFlags
+///
+/// The flags stored in the upper bits of a frame's id word.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct FrameFlags(u32);
+
+impl FrameFlags {
+    /// Extended frame format (29 bit id)
+    pub const EFF: FrameFlags = FrameFlags(EFF_FLAG);
+    /// Remote transmission request
+    pub const RTR: FrameFlags = FrameFlags(RTR_FLAG);
+    /// Error frame
+    pub const ERR: FrameFlags = FrameFlags(ERR_FLAG);
+
+    /// No flags set
+    #[inline]
+    pub fn empty() -> FrameFlags {
+        FrameFlags(0)
+    }
+
+    /// Flags from the bits of an id word, ignoring the id itself.
+    #[inline]
+    pub fn from_bits_truncate(bits: u32) -> FrameFlags {
+        FrameFlags(bits & (EFF_FLAG | RTR_FLAG | ERR_FLAG))
+    }
+
+    /// The flags as bits of an id word
+    #[inline]
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Check if all flags in `other` are set
+    #[inline]
+    pub fn contains(&self, other: FrameFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Check if no flags are set
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl ops::BitOr for FrameFlags {
+    type Output = FrameFlags;
+
+    fn bitor(self, other: FrameFlags) -> FrameFlags {
+        FrameFlags(self.0 | other.0)
+    }
+}
+
+impl ops::BitOrAssign for FrameFlags {
+    fn bitor_assign(&mut self, other: FrameFlags) {
+        self.0 |= other.0;
+    }
+}
+
+impl ops::BitAnd for FrameFlags {
+    type Output = FrameFlags;
+
+    fn bitand(self, other: FrameFlags) -> FrameFlags {
+        FrameFlags(self.0 & other.0)
+    }
+}
+
+/// Error state of a CAN node
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorState {
+    /// The node takes part in bus communication normally
+    ErrorActive,
+    /// The node detected too many errors and may only signal them passively
+    ErrorPassive,
+}
+
+/// CanFdFrame
+///
+/// A CAN FD frame with up to 64 bytes of payload. Uses the memory layout of
+/// the kernel's `struct canfd_frame`.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct CanFdFrame {
+    /// 32 bit CAN_ID + EFF/RTR/ERR flags
+    _id: u32,
+    /// data length. Bytes beyond are not valid
+    _len: u8,
+    /// additional flags for CAN FD (BRS/ESI)
+    _flags: u8,
+    /// reserved
+    _res0: u8,
+    /// reserved
+    _res1: u8,
+    /// buffer for data
+    _data: [u8; CANFD_MAX_DLEN],
+}
+
+const _: () = assert!(core::mem::size_of::<CanFdFrame>() == CANFD_MTU);
+
+impl CanFdFrame {
+    /// Construct a new CAN FD frame.
+    ///
+    /// As with `CanFrame::new`, ids larger than 11 bits result in an
+    /// extended frame. `brs` enables the bit rate switch for the payload,
+    /// `esi` sets the error state indicator.
+    ///
+    /// Payloads between the valid CAN FD lengths are padded with zeros to
+    /// the next valid length, see `dlc::fd_len_to_dlc`.
+    pub fn new(id: u32, data: &[u8], brs: bool, esi: bool) -> Result<CanFdFrame, ConstructionError> {
+        let mut _id = id;
+
+        let dlc = if data.len() > CANFD_MAX_DLEN { None } else { fd_len_to_dlc(data.len() as u8) };
+        let len = match dlc {
+            Some(dlc) => fd_dlc_to_len(dlc),
+            None => return Err(ConstructionError::TooMuchData(data.len())),
+        };
+
+        if id > EFF_MASK {
+            return Err(ConstructionError::IDTooLarge(id));
+        }
+
+        // set EFF_FLAG on large message
+        if id > SFF_MASK {
+            _id |= EFF_FLAG;
+        }
+
+        let mut flags = 0;
+
+        if brs {
+            flags |= CANFD_BRS;
+        }
+
+        if esi {
+            flags |= CANFD_ESI;
+        }
+
+        let mut full_data = [0; CANFD_MAX_DLEN];
+        full_data[..data.len()].copy_from_slice(data);
+
+        Ok(CanFdFrame {
+               _id,
+               _len: len,
+               _flags: flags,
+               _res0: 0,
+               _res1: 0,
+               _data: full_data,
+           })
+    }
+
+    /// Return the actual CAN ID (without EFF/RTR/ERR flags)
+    #[inline]
+    pub fn id(&self) -> u32 {
+        if self.is_extended() {
+            self._id & EFF_MASK
+        } else {
+            self._id & SFF_MASK
+        }
+    }
+
+    /// Return the raw 32 bit id word, including EFF/RTR/ERR flags
+    #[inline]
+    pub fn raw_id(&self) -> u32 {
+        self._id
+    }
+
+    /// Check if frame uses 29 bit extended frame format
+    #[inline]
+    pub fn is_extended(&self) -> bool {
+        self._id & EFF_FLAG != 0
+    }
+
+    /// Check if frame is an error message
+    #[inline]
+    pub fn is_error(&self) -> bool {
+        self._id & ERR_FLAG != 0
+    }
+
+    /// Check if the bit rate switch is set
+    #[inline]
+    pub fn is_brs(&self) -> bool {
+        self._flags & CANFD_BRS != 0
+    }
+
+    /// Check if the error state indicator is set
+    ///
+    /// The transmitting node sets ESI while it is error passive, i.e. after
+    /// its error counters crossed the passive threshold of 128.
+    #[inline]
+    pub fn is_esi(&self) -> bool {
+        self._flags & CANFD_ESI != 0
+    }
+
+    /// Error state of the transmitting node, as reported by the ESI bit.
+    ///
+    /// This comes with every received FD frame and allows passive monitoring
+    /// of remote nodes' health without error frames.
+    #[inline]
+    pub fn error_state_hint(&self) -> ErrorState {
+        if self.is_esi() {
+            ErrorState::ErrorPassive
+        } else {
+            ErrorState::ErrorActive
+        }
+    }
+
+    /// A slice into the actual data. Slice will always be <= 64 bytes in
+    /// length
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self._data[..(self._len as usize).min(CANFD_MAX_DLEN)]
+    }
+
+    /// Interpret the frame buffer as holding a classic CAN frame.
+    ///
+    /// The kernel writes classic frames into the first 16 bytes, which share
+    /// the layout of `CanFrame`.
+    #[cfg(feature = "std")]
+    pub(crate) fn as_classic(&self) -> CanFrame {
+        let mut data = [0; 8];
+        data.copy_from_slice(&self._data[..8]);
+
+        CanFrame {
+            _id: self._id,
+            _data_len: self._len,
+            _pad: self._flags,
+            _res0: self._res0,
+            _res1: self._res1,
+            _data: data,
+        }
+    }
+}
+
+/// Upcast a classic frame, keeping id, flags and payload. BRS and ESI are
+/// cleared.
+impl From<CanFrame> for CanFdFrame {
+    fn from(frame: CanFrame) -> CanFdFrame {
+        let mut data = [0; CANFD_MAX_DLEN];
+        data[..8].copy_from_slice(&frame._data);
+
+        CanFdFrame {
+            _id: frame._id,
+            _len: frame._data_len,
+            _flags: 0,
+            _res0: 0,
+            _res1: 0,
+            _data: data,
+        }
+    }
+}
+
+/// Downcast to a classic frame, failing with `TooMuchData` if the payload is
+/// longer than 8 bytes. BRS and ESI are dropped.
+impl TryFrom<CanFdFrame> for CanFrame {
+    type Error = ConstructionError;
+
+    fn try_from(frame: CanFdFrame) -> Result<CanFrame, ConstructionError> {
+        if frame._len as usize > 8 {
+            return Err(ConstructionError::TooMuchData(frame._len as usize));
+        }
+
+        let mut data = [0; 8];
+        data.copy_from_slice(&frame._data[..8]);
+
+        Ok(CanFrame {
+            _id: frame._id,
+            _data_len: frame._len,
+            _pad: 0,
+            _res0: 0,
+            _res1: 0,
+            _data: data,
+        })
+    }
+}
+
+/// AnyFrame
+///
+/// A frame read from a socket with CAN FD frames enabled, which may receive
+/// classic and FD frames alike.
+#[derive(Debug, Copy, Clone)]
+pub enum AnyFrame {
+    /// A classic CAN frame
+    Classic(CanFrame),
+    /// A CAN FD frame
+    Fd(CanFdFrame),
+}
+
+impl From<CanFrame> for AnyFrame {
+    fn from(frame: CanFrame) -> AnyFrame {
+        AnyFrame::Classic(frame)
+    }
+}
+
+impl From<CanFdFrame> for AnyFrame {
+    fn from(frame: CanFdFrame) -> AnyFrame {
+        AnyFrame::Fd(frame)
+    }
+}
+
+/// CanXlFrame
+///
+/// A CAN XL frame with up to 2048 bytes of payload. Like `CanFrame`, uses the
+/// memory layout of the kernel's `struct canxl_frame`.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct CanXlFrame {
+    /// 11 bit priority for arbitration and 8 bit VCID
+    _prio: u32,
+    /// additional flags for CAN XL
+    _flags: u8,
+    /// SDU (service data unit) type
+    _sdt: u8,
+    /// data length. Bytes beyond are not valid
+    _len: u16,
+    /// acceptance field
+    _af: u32,
+    /// buffer for data
+    _data: [u8; CANXL_MAX_DLEN],
+}
+
+const _: () = assert!(core::mem::size_of::<CanXlFrame>() == CANXL_HDR_SIZE + CANXL_MAX_DLEN);
+
+impl CanXlFrame {
+    /// Construct a new CAN XL frame.
+    ///
+    /// `prio` is the 11 bit arbitration priority, `sdt` the SDU type and `af`
+    /// the acceptance field. The payload must be between 1 and 2048 bytes.
+    pub fn new(prio: u32, sdt: u8, af: u32, data: &[u8]) -> Result<CanXlFrame, ConstructionError> {
+        if data.len() < CANXL_MIN_DLEN {
+            return Err(ConstructionError::TooLittleData(data.len()));
+        }
+
+        if data.len() > CANXL_MAX_DLEN {
+            return Err(ConstructionError::TooMuchData(data.len()));
+        }
+
+        if prio > CANXL_PRIO_MASK {
+            return Err(ConstructionError::IDTooLarge(prio));
+        }
+
+        let mut full_data = [0; CANXL_MAX_DLEN];
+        full_data[..data.len()].copy_from_slice(data);
+
+        Ok(CanXlFrame {
+               _prio: prio,
+               _flags: CANXL_XLF,
+               _sdt: sdt,
+               _len: data.len() as u16,
+               _af: af,
+               _data: full_data,
+           })
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn empty() -> CanXlFrame {
+        CanXlFrame {
+            _prio: 0,
+            _flags: 0,
+            _sdt: 0,
+            _len: 0,
+            _af: 0,
+            _data: [0; CANXL_MAX_DLEN],
+        }
+    }
+
+    /// Return the 11 bit arbitration priority
+    #[inline]
+    pub fn priority(&self) -> u32 {
+        self._prio & CANXL_PRIO_MASK
+    }
+
+    /// Return the virtual CAN network identifier
+    #[inline]
+    pub fn vcid(&self) -> u8 {
+        (self._prio >> CANXL_VCID_OFFSET) as u8
+    }
+
+    /// Return the SDU type
+    #[inline]
+    pub fn sdt(&self) -> u8 {
+        self._sdt
+    }
+
+    /// Return the acceptance field
+    #[inline]
+    pub fn af(&self) -> u32 {
+        self._af
+    }
+
+    /// Check if the simple extended content flag is set
+    #[inline]
+    pub fn is_sec(&self) -> bool {
+        self._flags & CANXL_SEC != 0
+    }
+
+    /// Check if the mandatory XL frame flag is set
+    #[inline]
+    pub fn is_xl(&self) -> bool {
+        self._flags & CANXL_XLF != 0
+    }
+
+    /// A slice into the actual data. Slice will always be <= 2048 bytes in
+    /// length
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self._data[..(self._len as usize).min(CANXL_MAX_DLEN)]
+    }
+
+    /// Number of bytes this frame occupies on the socket (header and data)
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn wire_len(&self) -> usize {
+        CANXL_HDR_SIZE + self.data().len()
+    }
+}
+
+impl fmt::Debug for CanXlFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CanXlFrame")
+            .field("prio", &self._prio)
+            .field("flags", &self._flags)
+            .field("sdt", &self._sdt)
+            .field("af", &self._af)
+            .field("data", &self.data())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CanFrame;
+    #[cfg(feature = "std")]
+    use core::mem;
+
+    #[test]
+    fn test_unused_data_is_zeroed() {
+        let frame = CanFrame::new(0x123, &[0xFF, 0xFF, 0xFF], false, false).unwrap();
+
+        assert_eq!(frame._data_len, 3);
+        assert_eq!(frame._data, [0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0]);
+
+        let frame = CanFrame::from_array(0x123, [0xFF; 8], 3).unwrap();
+        assert_eq!(frame._data, [0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0]);
+
+        const FRAME: CanFrame = CanFrame::const_new(0x123, [0xFF; 8], 3);
+        assert_eq!(FRAME._data, [0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_layout_offsets() {
+        let frame = CanFrame::empty();
+        let base = &frame as *const CanFrame as usize;
+
+        assert_eq!(&frame._id as *const u32 as usize - base, 0);
+        assert_eq!(&frame._data_len as *const u8 as usize - base, 4);
+        assert_eq!(frame._data.as_ptr() as usize - base, 8);
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_libc_layout() {
+        let frame = CanFrame::new(0x123, &[1, 2, 3], false, false).unwrap();
+        let raw = libc::can_frame::from(frame);
+
+        let offset = |field: *const u8, base: *const u8| field as usize - base as usize;
+        let base = &frame as *const CanFrame as *const u8;
+        let raw_base = &raw as *const libc::can_frame as *const u8;
+
+        assert_eq!(mem::size_of::<CanFrame>(), mem::size_of::<libc::can_frame>());
+        assert_eq!(offset(&frame._data_len, base), offset(&raw.can_dlc, raw_base));
+        assert_eq!(offset(&frame._res1, base), offset(&raw.len8_dlc, raw_base));
+        assert_eq!(offset(frame._data.as_ptr(), base), offset(raw.data.as_ptr(), raw_base));
+
+        assert_eq!(CanFrame::from(raw), frame);
     }
 }
@@ -0,0 +1,529 @@
+//! Information about CAN network interfaces and their link state.
+
+use std::{fs, io, mem};
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::PathBuf;
+
+use log::debug;
+
+use errors::CanSocketOpenError;
+use util::if_nametoindex;
+use constants::*;
+
+/// A CAN network interface, such as "can0".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanInterface {
+    name: String,
+    if_index: u32,
+}
+
+impl CanInterface {
+    /// Look up a named CAN interface.
+    pub fn open(ifname: &str) -> Result<CanInterface, CanSocketOpenError> {
+        let if_index = if_nametoindex(ifname)?;
+
+        Ok(CanInterface {
+            name: ifname.to_owned(),
+            if_index,
+        })
+    }
+
+    /// Name of the interface.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Kernel interface number.
+    pub fn index(&self) -> u32 {
+        self.if_index
+    }
+
+    /// Configured length of the transmit queue (`txqueuelen`), in frames.
+    ///
+    /// Writes start failing with `ENOBUFS` or `EAGAIN` once this many frames
+    /// are waiting to be sent. The kernel does not report the current number
+    /// of queued frames for CAN interfaces.
+    pub fn tx_queue_len(&self) -> io::Result<u32> {
+        let len = fs::read_to_string(self.sysfs_path("tx_queue_len"))?;
+
+        len.trim()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Check if the controller is in one-shot mode, in which frames are not
+    /// retransmitted after errors or missing acknowledgement.
+    ///
+    /// One-shot mode is configured with `ip link set can0 type can one-shot
+    /// on`. Fails with `Unsupported` for interfaces without a configurable
+    /// controller, such as vcan.
+    pub fn is_one_shot(&self) -> io::Result<bool> {
+        Ok(can_ctrlmode(self.if_index)? & CAN_CTRLMODE_ONE_SHOT != 0)
+    }
+
+    /// Bring the interface up, like `ip link set can0 up`.
+    ///
+    /// Requires the `CAP_NET_ADMIN` capability.
+    pub fn bring_up(&self) -> io::Result<()> {
+        self.set_up(true)
+    }
+
+    /// Bring the interface down, like `ip link set can0 down`.
+    ///
+    /// Requires the `CAP_NET_ADMIN` capability.
+    pub fn bring_down(&self) -> io::Result<()> {
+        self.set_up(false)
+    }
+
+    fn set_up(&self, up: bool) -> io::Result<()> {
+        let flags = if up { libc::IFF_UP as u32 } else { 0 };
+        let req = link_request(libc::RTM_NEWLINK, NLM_F_REQUEST | NLM_F_ACK, self.if_index, flags,
+                               libc::IFF_UP as u32, &[]);
+        nl_transact(&req)
+    }
+
+    fn sysfs_path(&self, attr: &str) -> PathBuf {
+        PathBuf::from("/sys/class/net").join(&self.name).join(attr)
+    }
+}
+
+impl<'a> TryFrom<&'a str> for CanInterface {
+    type Error = CanSocketOpenError;
+
+    fn try_from(ifname: &'a str) -> Result<CanInterface, CanSocketOpenError> {
+        CanInterface::open(ifname)
+    }
+}
+
+/// A change of the link state of a CAN interface, see `CanLinkMonitor`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkEvent {
+    /// The interface was brought up and is running.
+    Up(CanInterface),
+    /// The interface went down or lost its carrier, e.g. after bus off.
+    Down(CanInterface),
+    /// The interface was removed.
+    Removed(CanInterface),
+}
+
+// struct sockaddr_nl
+#[derive(Debug)]
+#[repr(C)]
+struct NlAddr {
+    nl_family: libc::sa_family_t,
+    _pad: libc::c_ushort,
+    nl_pid: u32,
+    nl_groups: u32,
+}
+
+const _: () = assert!(mem::size_of::<NlAddr>() == 12);
+
+// sizes of struct nlmsghdr and struct ifinfomsg
+const NLMSG_HDRLEN: usize = 16;
+const IFINFOMSG_LEN: usize = 16;
+
+/// A netlink route socket, closed on drop.
+#[derive(Debug)]
+struct NlSocket {
+    fd: libc::c_int,
+}
+
+impl NlSocket {
+    /// Open a socket subscribed to the multicast `groups`.
+    fn open(groups: u32) -> io::Result<NlSocket> {
+        let fd = unsafe {
+            libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_ROUTE)
+        };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // dropping the socket cleans up the resource if failure to open
+        let socket = NlSocket { fd };
+
+        let addr = NlAddr {
+            nl_family: libc::AF_NETLINK as libc::sa_family_t,
+            _pad: 0,
+            nl_pid: 0,
+            nl_groups: groups,
+        };
+        let r = unsafe {
+            libc::bind(fd,
+                       &addr as *const NlAddr as *const libc::sockaddr,
+                       mem::size_of::<NlAddr>() as libc::socklen_t)
+        };
+
+        if r == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(socket)
+    }
+
+    fn send(&self, msg: &[u8]) -> io::Result<()> {
+        let r = unsafe { libc::send(self.fd, msg.as_ptr() as *const libc::c_void, msg.len(), 0) };
+
+        if r as usize != msg.len() {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let r = unsafe { libc::recv(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+
+        if r < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(r as usize)
+    }
+}
+
+impl Drop for NlSocket {
+    fn drop(&mut self) {
+        if unsafe { libc::close(self.fd) } == -1 {
+            debug!("Error dropping netlink socket {}", io::Error::last_os_error());
+        }
+    }
+}
+
+/// Controller mode flags (`CAN_CTRLMODE_*`) of a CAN interface.
+pub(crate) fn can_ctrlmode(if_index: u32) -> io::Result<u32> {
+    let socket = NlSocket::open(0)?;
+
+    socket.send(&link_request(libc::RTM_GETLINK, NLM_F_REQUEST, if_index, 0, 0, &[]))?;
+
+    let mut buf = [0u8; 8192];
+    let len = socket.recv(&mut buf)?;
+    let (ty, msg) = nl_messages(&buf[..len])
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "empty netlink response"))?;
+
+    if ty == NLMSG_ERROR {
+        let errno = match msg.get(..4) {
+            Some(b) => i32::from_ne_bytes([b[0], b[1], b[2], b[3]]),
+            None => -libc::EINVAL,
+        };
+        return Err(io::Error::from_raw_os_error(-errno));
+    }
+
+    msg.get(IFINFOMSG_LEN..)
+        .and_then(|attrs| nl_attr(attrs, IFLA_LINKINFO))
+        .and_then(|info| nl_attr(info, IFLA_INFO_DATA))
+        .and_then(|data| nl_attr(data, IFLA_CAN_CTRLMODE))
+        // struct can_ctrlmode { mask, flags }
+        .and_then(|mode| mode.get(4..8))
+        .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "interface has no CAN controller mode"))
+}
+
+/// Build a link message: struct nlmsghdr, struct ifinfomsg and `attrs`.
+///
+/// Of the interface flags, only those in `ifi_change` are set to their
+/// value in `ifi_flags`.
+fn link_request(ty: u16, flags: u16, if_index: u32, ifi_flags: u32, ifi_change: u32, attrs: &[u8]) -> Vec<u8> {
+    let len = NLMSG_HDRLEN + IFINFOMSG_LEN + attrs.len();
+
+    let mut req = Vec::with_capacity(len);
+    req.extend_from_slice(&(len as u32).to_ne_bytes());
+    req.extend_from_slice(&ty.to_ne_bytes());
+    req.extend_from_slice(&flags.to_ne_bytes());
+    req.extend_from_slice(&[0; 8]);
+
+    req.extend_from_slice(&[libc::AF_UNSPEC as u8, 0, 0, 0]);
+    req.extend_from_slice(&if_index.to_ne_bytes());
+    req.extend_from_slice(&ifi_flags.to_ne_bytes());
+    req.extend_from_slice(&ifi_change.to_ne_bytes());
+
+    req.extend_from_slice(attrs);
+    req
+}
+
+/// Iterate over the type and payload of the netlink messages in `buf`.
+fn nl_messages(mut buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        if buf.len() < NLMSG_HDRLEN {
+            return None;
+        }
+
+        let len = u32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        let ty = u16::from_ne_bytes([buf[4], buf[5]]);
+
+        if len < NLMSG_HDRLEN || len > buf.len() {
+            return None;
+        }
+
+        let msg = &buf[NLMSG_HDRLEN..len];
+        buf = &buf[nl_align(len).min(buf.len())..];
+        Some((ty, msg))
+    })
+}
+
+/// Payload of the first attribute of type `ty` in `attrs`.
+fn nl_attr(mut attrs: &[u8], ty: u16) -> Option<&[u8]> {
+    while attrs.len() >= 4 {
+        let len = u16::from_ne_bytes([attrs[0], attrs[1]]) as usize;
+        // ignore the nested and byte order flags
+        let attr_ty = u16::from_ne_bytes([attrs[2], attrs[3]]) & 0x3FFF;
+
+        if len < 4 || len > attrs.len() {
+            return None;
+        }
+
+        if attr_ty == ty {
+            return Some(&attrs[4..len]);
+        }
+
+        attrs = &attrs[nl_align(len).min(attrs.len())..];
+    }
+
+    None
+}
+
+/// VcanGuard
+///
+/// A virtual CAN interface for tests, created and brought up on
+/// construction and removed on drop.
+///
+/// Creating interfaces requires the `CAP_NET_ADMIN` capability and the vcan
+/// kernel module. Tests should skip if `create` fails with
+/// `PermissionDenied` or `Unsupported`.
+///
+/// ```no_run
+/// use socketcan::interface::VcanGuard;
+/// use socketcan::socket::CanSocket;
+///
+/// let vcan = match VcanGuard::create("vcantest0") {
+///     Ok(vcan) => vcan,
+///     Err(e) => return println!("skipping test: {}", e),
+/// };
+/// let socket = CanSocket::open(vcan.name()).unwrap();
+/// ```
+#[cfg(any(test, feature = "test-utils"))]
+#[derive(Debug)]
+pub struct VcanGuard {
+    interface: CanInterface,
+}
+
+#[cfg(any(test, feature = "test-utils"))]
+impl VcanGuard {
+    /// Create and bring up the vcan interface `name`.
+    ///
+    /// Fails with `AlreadyExists` if an interface with that name exists.
+    pub fn create(name: &str) -> io::Result<VcanGuard> {
+        const NLM_F_EXCL: u16 = 0x200;
+        const NLM_F_CREATE: u16 = 0x400;
+        const IFLA_INFO_KIND: u16 = 1;
+
+        let mut ifname = name.as_bytes().to_vec();
+        ifname.push(0);
+
+        let mut info = Vec::new();
+        nl_push_attr(&mut info, IFLA_INFO_KIND, b"vcan\0");
+
+        let mut name_attr = Vec::new();
+        nl_push_attr(&mut name_attr, IFLA_IFNAME, &ifname);
+
+        let mut attrs = name_attr.clone();
+        nl_push_attr(&mut attrs, IFLA_LINKINFO, &info);
+
+        let flags = NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE | NLM_F_EXCL;
+        let up = libc::IFF_UP as u32;
+        nl_transact(&link_request(libc::RTM_NEWLINK, flags, 0, up, up, &attrs))?;
+
+        match CanInterface::open(name) {
+            Ok(interface) => Ok(VcanGuard { interface }),
+            Err(e) => {
+                // do not leave the interface behind, it is removed by name
+                let req = link_request(libc::RTM_DELLINK, NLM_F_REQUEST | NLM_F_ACK, 0, 0, 0, &name_attr);
+                if let Err(e) = nl_transact(&req) {
+                    debug!("Error removing {}: {}", name, e);
+                }
+
+                Err(match e {
+                    CanSocketOpenError::IOError(e) => e,
+                    e => io::Error::other(e.to_string()),
+                })
+            }
+        }
+    }
+
+    /// Name of the interface.
+    pub fn name(&self) -> &str {
+        self.interface.name()
+    }
+
+    /// The created interface.
+    pub fn interface(&self) -> &CanInterface {
+        &self.interface
+    }
+}
+
+#[cfg(any(test, feature = "test-utils"))]
+impl Drop for VcanGuard {
+    fn drop(&mut self) {
+        let req = link_request(libc::RTM_DELLINK, NLM_F_REQUEST | NLM_F_ACK, self.interface.index(), 0, 0, &[]);
+        if let Err(e) = nl_transact(&req) {
+            debug!("Error removing {}: {}", self.interface.name(), e);
+        }
+    }
+}
+
+/// Append an attribute to `buf`, padded to 4 bytes.
+#[cfg(any(test, feature = "test-utils"))]
+fn nl_push_attr(buf: &mut Vec<u8>, ty: u16, payload: &[u8]) {
+    buf.extend_from_slice(&((4 + payload.len()) as u16).to_ne_bytes());
+    buf.extend_from_slice(&ty.to_ne_bytes());
+    buf.extend_from_slice(payload);
+    buf.resize(nl_align(buf.len()), 0);
+}
+
+/// Send a request asking for an acknowledgement and wait for it.
+fn nl_transact(req: &[u8]) -> io::Result<()> {
+    let socket = NlSocket::open(0)?;
+    socket.send(req)?;
+
+    let mut buf = [0u8; 8192];
+    let len = socket.recv(&mut buf)?;
+
+    let ack = nl_messages(&buf[..len]).next();
+    match ack {
+        Some((NLMSG_ERROR, msg)) if msg.len() >= 4 => {
+            match i32::from_ne_bytes([msg[0], msg[1], msg[2], msg[3]]) {
+                0 => Ok(()),
+                errno => Err(io::Error::from_raw_os_error(-errno)),
+            }
+        }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "missing netlink acknowledgement")),
+    }
+}
+
+/// CanLinkMonitor
+///
+/// Reports CAN interfaces going up, down or away using the kernel's
+/// rtnetlink link notifications. Changes to other types of interfaces are
+/// ignored.
+#[derive(Debug)]
+pub struct CanLinkMonitor {
+    socket: NlSocket,
+    running: HashMap<u32, bool>,
+    pub(crate) pending: VecDeque<LinkEvent>,
+}
+
+impl CanLinkMonitor {
+    /// Subscribe to link notifications.
+    ///
+    /// The current state of all CAN interfaces is read first, so only
+    /// changes after opening are reported.
+    pub fn open() -> io::Result<CanLinkMonitor> {
+        let mut monitor = CanLinkMonitor {
+            socket: NlSocket::open(libc::RTMGRP_LINK as u32)?,
+            running: HashMap::new(),
+            pending: VecDeque::new(),
+        };
+
+        // subscribed before the dump, so no change in between is missed
+        monitor.load_links()?;
+        monitor.pending.clear();
+
+        Ok(monitor)
+    }
+
+    // record the state of every interface from an RTM_GETLINK dump
+    fn load_links(&mut self) -> io::Result<()> {
+        let socket = NlSocket::open(0)?;
+        socket.send(&link_request(libc::RTM_GETLINK, NLM_F_REQUEST | NLM_F_DUMP, 0, 0, 0, &[]))?;
+
+        // the kernel fills dump replies up to the size of the receive buffer
+        let mut buf = vec![0u8; 32768];
+        loop {
+            let len = socket.recv(&mut buf)?;
+            if len == 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "empty netlink response"));
+            }
+
+            for (ty, msg) in nl_messages(&buf[..len]) {
+                match ty {
+                    NLMSG_DONE => return Ok(()),
+                    NLMSG_ERROR => {
+                        let errno = match msg.get(..4) {
+                            Some(b) => i32::from_ne_bytes([b[0], b[1], b[2], b[3]]),
+                            None => -libc::EINVAL,
+                        };
+                        return Err(io::Error::from_raw_os_error(-errno));
+                    }
+                    libc::RTM_NEWLINK => self.parse_link(ty, msg),
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Block until the link state of a CAN interface changes.
+    ///
+    /// Notifications that do not change whether an interface is running,
+    /// such as MTU changes, are skipped.
+    pub fn next_event(&mut self) -> io::Result<LinkEvent> {
+        let mut buf = [0u8; 8192];
+
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(event);
+            }
+
+            let len = self.socket.recv(&mut buf)?;
+            self.parse(&buf[..len]);
+        }
+    }
+
+    pub(crate) fn parse(&mut self, buf: &[u8]) {
+        for (ty, msg) in nl_messages(buf) {
+            if ty == libc::RTM_NEWLINK || ty == libc::RTM_DELLINK {
+                self.parse_link(ty, msg);
+            }
+        }
+    }
+
+    fn parse_link(&mut self, ty: u16, msg: &[u8]) {
+        if msg.len() < IFINFOMSG_LEN || u16::from_ne_bytes([msg[2], msg[3]]) != ARPHRD_CAN {
+            return;
+        }
+
+        let if_index = u32::from_ne_bytes([msg[4], msg[5], msg[6], msg[7]]);
+        let flags = u32::from_ne_bytes([msg[8], msg[9], msg[10], msg[11]]);
+
+        let name = nl_attr(&msg[IFINFOMSG_LEN..], IFLA_IFNAME)
+            .and_then(|name| CStr::from_bytes_until_nul(name).ok())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let can_if = CanInterface { name, if_index };
+
+        if ty == libc::RTM_DELLINK {
+            self.running.remove(&if_index);
+            self.pending.push_back(LinkEvent::Removed(can_if));
+            return;
+        }
+
+        let running = flags & libc::IFF_UP as u32 != 0 && flags & libc::IFF_RUNNING as u32 != 0;
+        if self.running.insert(if_index, running) != Some(running) {
+            self.pending.push_back(if running { LinkEvent::Up(can_if) } else { LinkEvent::Down(can_if) });
+        }
+    }
+}
+
+// netlink messages and attributes are aligned to 4 bytes
+fn nl_align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+impl AsRawFd for CanLinkMonitor {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.fd
+    }
+}
@@ -0,0 +1,181 @@
+//! SAE J1939 sockets.
+//!
+//! J1939 addresses messages by parameter group number (PGN) and 8 bit node
+//! addresses. The kernel's `CAN_J1939` protocol takes care of splitting
+//! messages longer than 8 bytes using the transport protocol and reassembling
+//! them on reception, so `send` and `recv` operate on complete messages.
+
+use std::{io, mem};
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use log::debug;
+
+use constants::*;
+pub use constants::{J1939_NO_ADDR, J1939_NO_NAME, J1939_PGN_MAX};
+use errors::CanSocketOpenError;
+use util::{if_nametoindex, set_socket_option};
+
+/// A `struct sockaddr_can` holding J1939 addressing information.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct J1939Addr {
+    af_can: libc::c_short,
+    if_index: libc::c_int,
+    name: u64,
+    pgn: u32,
+    addr: u8,
+}
+
+// must match the kernel's struct sockaddr_can
+const _: () = assert!(mem::size_of::<J1939Addr>() == 24);
+
+impl J1939Addr {
+    fn new(if_index: libc::c_int, name: u64, pgn: u32, addr: u8) -> J1939Addr {
+        J1939Addr {
+            af_can: libc::AF_CAN as libc::c_short,
+            if_index,
+            name,
+            pgn,
+            addr,
+        }
+    }
+}
+
+/// A J1939 message received from the bus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct J1939Message {
+    /// Parameter group number
+    pub pgn: u32,
+    /// Address of the sender
+    pub source: u8,
+    /// 64 bit NAME of the sender, `J1939_NO_NAME` if unknown
+    pub name: u64,
+    /// Payload, reassembled if sent using the transport protocol
+    pub data: Vec<u8>,
+}
+
+/// A socket for sending and receiving J1939 messages.
+///
+/// Will be closed upon deallocation.
+#[derive(Debug)]
+pub struct J1939Socket {
+    fd: libc::c_int,
+}
+
+impl J1939Socket {
+    /// Open a named CAN device using the node address `addr`.
+    ///
+    /// Broadcasts are enabled, so messages can be sent to `J1939_NO_ADDR`
+    /// (255).
+    pub fn open(ifname: &str, addr: u8) -> Result<J1939Socket, CanSocketOpenError> {
+        let if_index = if_nametoindex(ifname)?;
+
+        let fd = unsafe { libc::socket(libc::PF_CAN, libc::SOCK_DGRAM, CAN_J1939) };
+        if fd == -1 {
+            return Err(CanSocketOpenError::from(io::Error::last_os_error()));
+        }
+
+        // dropping the socket cleans up the resource if failure to open
+        let socket = J1939Socket { fd };
+        set_socket_option(fd, libc::SOL_SOCKET, libc::SO_BROADCAST, &(1 as libc::c_int))?;
+
+        let local = J1939Addr::new(if_index as libc::c_int, J1939_NO_NAME, J1939_NO_PGN, addr);
+        let r = unsafe {
+            libc::bind(fd,
+                       &local as *const J1939Addr as *const libc::sockaddr,
+                       mem::size_of::<J1939Addr>() as libc::socklen_t)
+        };
+
+        if r == -1 {
+            return Err(CanSocketOpenError::from(io::Error::last_os_error()));
+        }
+
+        Ok(socket)
+    }
+
+    /// Send `data` with parameter group `pgn` to the node `dest`.
+    ///
+    /// Use `J1939_NO_ADDR` (255) as destination to broadcast. Payloads
+    /// longer than 8 bytes are sent using the transport protocol.
+    pub fn send(&self, pgn: u32, dest: u8, data: &[u8]) -> io::Result<()> {
+        if pgn > J1939_PGN_MAX {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "PGN out of range"));
+        }
+
+        let peer = J1939Addr::new(0, J1939_NO_NAME, pgn, dest);
+        let r = unsafe {
+            libc::sendto(self.fd,
+                         data.as_ptr() as *const libc::c_void,
+                         data.len(),
+                         0,
+                         &peer as *const J1939Addr as *const libc::sockaddr,
+                         mem::size_of::<J1939Addr>() as libc::socklen_t)
+        };
+
+        if r as usize != data.len() {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Blocking receive a single message.
+    ///
+    /// Messages larger than the transport protocol maximum of 1785 bytes
+    /// (sent using the extended transport protocol) fail with `InvalidData`.
+    pub fn recv(&self) -> io::Result<J1939Message> {
+        let mut buf = vec![0u8; J1939_MAX_TP_PACKET_SIZE];
+        let mut peer: J1939Addr = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<J1939Addr>() as libc::socklen_t;
+
+        let r = unsafe {
+            libc::recvfrom(self.fd,
+                           buf.as_mut_ptr() as *mut libc::c_void,
+                           buf.len(),
+                           libc::MSG_TRUNC,
+                           &mut peer as *mut J1939Addr as *mut libc::sockaddr,
+                           &mut len)
+        };
+
+        if r == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if r as usize > buf.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "J1939 message truncated"));
+        }
+
+        buf.truncate(r as usize);
+
+        Ok(J1939Message {
+            pgn: peer.pgn,
+            source: peer.addr,
+            name: peer.name,
+            data: buf,
+        })
+    }
+
+    /// Receive messages addressed to any node, not only this one.
+    pub fn set_promiscuous(&self, enabled: bool) -> io::Result<()> {
+        let promisc: libc::c_int = if enabled { 1 } else { 0 };
+        set_socket_option(self.fd, SOL_CAN_J1939, SO_J1939_PROMISC, &promisc)
+    }
+}
+
+impl AsRawFd for J1939Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for J1939Socket {
+    fn drop(&mut self) {
+        let r = unsafe { libc::close(self.fd) };
+
+        if r == -1 {
+            debug!("Error dropping J1939 socket {}", io::Error::last_os_error());
+        } else {
+            debug!("J1939 socket dropped (fd: {})", self.fd);
+        }
+    }
+}
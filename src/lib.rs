@@ -22,7 +22,8 @@
 //! The Linux socketcan subsystem makes the CAN bus available as a regular
 //! networking device. Opening an network interface allows receiving all CAN
 //! messages received on it. A device CAN be opened multiple times, every
-//! client will receive all CAN frames simultaneously.
+//! client will receive all CAN frames simultaneously. `socket::CanBus` hands
+//! out such independent sockets, each with its own set of filters.
 //!
 //! Similarly, CAN frames can be sent to the bus by multiple client
 //! simultaneously as well.
@@ -40,19 +41,53 @@
 //! Raw access to the underlying file descriptor and construction through
 //! is available through the `AsRawFd`, `IntoRawFd` and `FromRawFd`
 //! implementations.
+//!
+//! # no_std
+//!
+//! The frame and filter types as well as error frame decoding do not depend
+//! on the standard library. Disabling the default `std` feature builds the
+//! crate as `no_std` (requiring `alloc`), leaving out the socket API.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
 
 pub extern crate libc;
+#[cfg(feature = "std")]
 pub extern crate nix;
+#[cfg(feature = "std")]
 pub extern crate itertools;
 pub extern crate byte_conv;
 pub extern crate log;
 
 mod constants;
-mod errors;
+pub mod errors;
+#[cfg(feature = "std")]
 mod util;
 pub mod frame;
+pub mod dlc;
+#[cfg(feature = "std")]
 pub mod socket;
-mod filter;
+#[cfg(feature = "std")]
+pub mod task;
+#[cfg(feature = "std")]
+pub mod bridge;
+#[cfg(feature = "std")]
+pub mod transport;
+#[cfg(feature = "std")]
+pub mod j1939;
+#[cfg(feature = "std")]
+pub mod interface;
+#[cfg(feature = "std")]
+pub mod busload;
+#[cfg(feature = "std")]
+pub mod worker;
+pub mod filter;
+pub mod cache;
+pub mod n2k;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests;
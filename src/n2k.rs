@@ -0,0 +1,170 @@
+//! NMEA 2000 fast packet transport.
+//!
+//! NMEA 2000 sends messages of up to 223 bytes as a series of up to 32
+//! frames sharing one id. The first data byte of every frame holds a 3 bit
+//! sequence id, identifying the message, and a 5 bit frame counter. The
+//! first frame additionally carries the total message length, followed by
+//! 6 bytes of payload; all other frames carry 7 bytes of payload.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::cmp;
+
+use constants::EFF_MASK;
+use errors::ConstructionError;
+use frame::CanFrame;
+
+/// Maximum payload of a fast packet message
+pub const FAST_PACKET_MAX_LEN: usize = 6 + 31 * 7;
+
+// unused bytes of the last frame
+const PADDING: u8 = 0xFF;
+
+/// A message reassembled from fast packet frames.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct N2kMessage {
+    /// Parameter group number
+    pub pgn: u32,
+    /// Address of the sending node
+    pub source: u8,
+    /// Message priority, 0 being the highest
+    pub priority: u8,
+    /// Message payload
+    pub data: Vec<u8>,
+}
+
+/// Parameter group number encoded in a 29 bit NMEA 2000 (J1939) id.
+///
+/// For destination specific PGNs (PDU1 format), the destination address is
+/// not part of the PGN.
+pub fn n2k_pgn(id: u32) -> u32 {
+    let pgn = (id >> 8) & 0x3FFFF;
+
+    if (pgn >> 8) & 0xFF < 240 {
+        pgn & 0x3FF00
+    } else {
+        pgn
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Partial {
+    sequence: u8,
+    next_frame: u8,
+    len: usize,
+    data: Vec<u8>,
+}
+
+/// N2kFastPacket
+///
+/// Reassembles fast packet messages from received frames. Messages are
+/// tracked per source address and PGN, so interleaved messages from
+/// different nodes are handled. A message is dropped if a frame is missing
+/// or arrives out of order.
+///
+/// Whether a PGN uses the fast packet protocol is defined by the NMEA 2000
+/// standard and cannot be told from the frames, so only frames of fast
+/// packet PGNs should be fed.
+#[derive(Debug, Clone, Default)]
+pub struct N2kFastPacket {
+    partial: BTreeMap<(u8, u32), Partial>,
+}
+
+impl N2kFastPacket {
+    /// Construct an assembler without any partial messages.
+    pub fn new() -> N2kFastPacket {
+        N2kFastPacket::default()
+    }
+
+    /// Process a received frame.
+    ///
+    /// Returns the message once its last frame has been received. Standard,
+    /// remote and error frames are ignored.
+    pub fn feed(&mut self, frame: &CanFrame) -> Option<N2kMessage> {
+        if !frame.is_extended() || frame.is_rtr() || frame.is_error() || frame.data().is_empty() {
+            return None;
+        }
+
+        let id = frame.id();
+        let key = (id as u8, n2k_pgn(id));
+        let data = frame.data();
+        let sequence = data[0] >> 5;
+        let counter = data[0] & 0x1F;
+
+        if counter == 0 {
+            if data.len() < 2 {
+                return None;
+            }
+
+            self.partial.insert(key, Partial {
+                sequence,
+                next_frame: 1,
+                len: data[1] as usize,
+                data: data[2..].to_vec(),
+            });
+        } else {
+            let partial = self.partial.get_mut(&key)?;
+
+            if partial.sequence != sequence || partial.next_frame != counter {
+                self.partial.remove(&key);
+                return None;
+            }
+
+            partial.next_frame += 1;
+            partial.data.extend_from_slice(&data[1..]);
+        }
+
+        if self.partial[&key].data.len() < self.partial[&key].len {
+            return None;
+        }
+
+        let mut partial = self.partial.remove(&key)?;
+        partial.data.truncate(partial.len);
+
+        Some(N2kMessage {
+            pgn: key.1,
+            source: key.0,
+            priority: ((id >> 26) & 0x7) as u8,
+            data: partial.data,
+        })
+    }
+
+    /// Number of messages currently being reassembled.
+    pub fn pending(&self) -> usize {
+        self.partial.len()
+    }
+
+    /// Split `payload` into fast packet frames sent with `id`.
+    ///
+    /// `sequence` (0 to 7) tells consecutive messages with the same id apart
+    /// and should be incremented for every message sent. All frames carry 8
+    /// bytes, the last one is padded with 0xFF.
+    pub fn segment(id: u32, sequence: u8, payload: &[u8]) -> Result<Vec<CanFrame>, ConstructionError> {
+        if id > EFF_MASK {
+            return Err(ConstructionError::IDTooLarge(id));
+        }
+
+        if payload.len() > FAST_PACKET_MAX_LEN {
+            return Err(ConstructionError::TooMuchData(payload.len()));
+        }
+
+        let sequence = (sequence & 0x7) << 5;
+        let mut frames = Vec::new();
+
+        let first = cmp::min(payload.len(), 6);
+        let mut data = [PADDING; 8];
+        data[0] = sequence;
+        data[1] = payload.len() as u8;
+        data[2..2 + first].copy_from_slice(&payload[..first]);
+        frames.push(CanFrame::new_extended(id, &data)?);
+
+        for (counter, chunk) in payload[first..].chunks(7).enumerate() {
+            let mut data = [PADDING; 8];
+            data[0] = sequence | (counter as u8 + 1);
+            data[1..1 + chunk.len()].copy_from_slice(chunk);
+            frames.push(CanFrame::new_extended(id, &data)?);
+        }
+
+        Ok(frames)
+    }
+}
@@ -1,41 +1,205 @@
-use std::{mem, io, time};
-use log::debug;
-use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::{cmp, fs, mem, io, ptr, thread, time};
+use std::sync::{Arc, Mutex};
+use std::ffi::CStr;
+use log::{debug, log, log_enabled, trace, Level};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
 
-use frame::CanFrame;
+use frame::{AnyFrame, CanFrame, CanFdFrame, CanXlFrame};
 use filter::CanFilter;
-use util::{set_socket_option, set_socket_option_mult, system_time_from_timespec, timeval_from_duration};
-use errors::CanSocketOpenError;
+use util::{find_cmsg, get_socket_option, if_nametoindex, is_tx_queue_full, set_socket_option, set_socket_option_mult, poll_fd, system_time_from_timespec, timeval_from_duration};
+use errors::{CanError, CanSocketOpenError};
+use task::{IsInterfaceDown, ShouldRetry};
+use interface::can_ctrlmode;
 use constants::*;
 
+pub use constants::{ERR_MASK_ALL, ERR_MASK_NONE};
+
 /// A socket for a CAN device.
 ///
 /// Will be closed upon deallocation. To close manually, use std::drop::Drop.
 /// Internally this is just a wrapped file-descriptor.
+///
+/// Errors closing the socket on drop are only logged, see
+/// `set_drop_log_level`. Call `close` first to handle them.
+///
+/// # Thread safety
+///
+/// `CanSocket` is `Send` and `Sync`. Every method maps to one or more
+/// syscalls on the file descriptor and the kernel delivers each frame to
+/// exactly one `read`, so sharing a `&CanSocket` between threads is memory
+/// safe. Methods issuing more than one syscall are not atomic though: `read`
+/// fetches the timestamp with a second call, which may return the timestamp
+/// of a frame read concurrently by another thread. Similarly, configuration
+/// helpers such as `drain` temporarily change socket options. Use `SharedCanSocket` if multiple threads need to read or write.
 #[derive(Debug)]
 pub struct CanSocket {
     fd: libc::c_int,
+    drop_log_level: Level,
+    timestamp_resolution: TimestampResolution,
+}
+
+/// Resolution of the receive timestamps returned by `CanSocket::read`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TimestampResolution {
+    /// Microseconds, retrieved using `SIOCGSTAMP` (default)
+    Micros,
+    /// Nanoseconds, retrieved using `SIOCGSTAMPNS`
+    Nanos,
 }
 
-/// A CAN address struct for binding a socket
+/// Flags reported by the kernel alongside a received frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RecvFlags {
+    /// The frame was sent by this socket and looped back (`MSG_CONFIRM`).
+    pub is_own: bool,
+    /// The frame was sent by a socket on this host (`MSG_DONTROUTE`).
+    pub is_local: bool,
+}
+
+/// A frame read from the bus, with error frames already decoded.
+#[derive(Debug, Copy, Clone)]
+pub enum CanEvent {
+    /// A data or remote frame.
+    Data(CanFrame),
+    /// An error frame, decoded into a `CanError`.
+    Error(CanError),
+}
+
+/// Iterator over the events read from a socket, see `CanSocket::events`.
 #[derive(Debug)]
+pub struct CanEvents<'a> {
+    socket: &'a CanSocket,
+}
+
+impl<'a> Iterator for CanEvents<'a> {
+    type Item = io::Result<CanEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = match self.socket.read_socket() {
+            Ok(frame) => frame,
+            Err(e) => return Some(Err(e)),
+        };
+
+        if !frame.is_error() {
+            return Some(Ok(CanEvent::Data(frame)));
+        }
+
+        Some(CanError::from_frame(&frame)
+                 .map(CanEvent::Error)
+                 .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+    }
+}
+
+/// CanAddr
+///
+/// The address a CAN socket is bound to, a `struct sockaddr_can`. Besides
+/// the interface, it holds the receive and transmit ids used by transport
+/// protocols such as ISOTP; they are zero for raw sockets.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(C)]
-struct CanAddr {
+pub struct CanAddr {
     af_can: libc::c_short,
     if_index: libc::c_int,
     rx_id: libc::c_uint, // transport protocol class address information (e.g. ISOTP)
     tx_id: libc::c_uint,
 }
 
+impl CanAddr {
+    /// Construct an address for the interface `if_index`, with both
+    /// transport protocol ids zero.
+    ///
+    /// An index of 0 addresses all CAN interfaces.
+    pub fn new(if_index: u32) -> CanAddr {
+        CanAddr {
+            af_can: libc::AF_CAN as libc::c_short,
+            if_index: if_index as libc::c_int,
+            rx_id: 0,
+            tx_id: 0,
+        }
+    }
+
+    /// Set the receive and transmit ids used by transport protocols.
+    pub fn with_tp_ids(mut self, rx_id: u32, tx_id: u32) -> CanAddr {
+        self.rx_id = rx_id;
+        self.tx_id = tx_id;
+        self
+    }
+
+    /// Kernel interface number, 0 for sockets bound to all interfaces.
+    pub fn if_index(&self) -> u32 {
+        self.if_index as u32
+    }
+
+    /// Transport protocol receive id.
+    pub fn rx_id(&self) -> u32 {
+        self.rx_id
+    }
+
+    /// Transport protocol transmit id.
+    pub fn tx_id(&self) -> u32 {
+        self.tx_id
+    }
+}
+
+/// A `struct ifreq` for querying the interface MTU
+#[repr(C)]
+struct IfReqMtu {
+    name: [libc::c_char; libc::IF_NAMESIZE],
+    mtu: libc::c_int,
+    _pad: [u8; 20],
+}
+
+const _: () = assert!(mem::size_of::<IfReqMtu>() == 40);
+
+// struct sockaddr_can up to and including the tp addresses
+const _: () = assert!(mem::size_of::<CanAddr>() == 16);
+
+// read_any receives into a struct canxl_frame and reads a canfd_frame from it
+const _: () = assert!(mem::align_of::<CanXlFrame>() >= mem::align_of::<CanFdFrame>());
+
+// control buffer in u64 words, with room for every control message a CAN
+// socket can attach at once: SO_RXQ_OVFL (24 bytes), SCM_TIMESTAMP and
+// SCM_TIMESTAMPNS (32 bytes each) and SCM_TIMESTAMPING (64 bytes)
+const CONTROL_LEN: usize = 24;
+
+// pause before retrying a write to a full TX queue, POLLOUT does not wait
+// for the queue to drain
+const TX_QUEUE_FULL_BACKOFF: time::Duration = time::Duration::from_millis(2);
+
 impl CanSocket {
     /// Open a named CAN device.
     ///
     /// Usually the more common case, opens a socket can device by name, such
     /// as "vcan0" or "socan0".
     pub fn open(ifname: &str) -> Result<CanSocket, CanSocketOpenError> {
-        match nix::net::if_::if_nametoindex(ifname) {
-            Ok(ifindex) => CanSocket::open_interface(ifindex),
-            Err(e) => Err(CanSocketOpenError::from(e)),
+        CanSocket::open_interface(if_nametoindex(ifname)?)
+    }
+
+    /// Open a named CAN device, receiving only frames with `id`.
+    ///
+    /// Ids up to 0x7FF match standard frames, larger ids extended frames.
+    /// The filter is installed before the socket is bound, so no other
+    /// frames are received.
+    pub fn open_filtered(ifname: &str, id: u32) -> Result<CanSocket, CanSocketOpenError> {
+        let filters = CanFilter::from_range(id, id)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        CanSocketBuilder::new().filters(&filters).open(ifname)
+    }
+
+    /// Open a socket bound to all CAN interfaces.
+    ///
+    /// Receives frames from every CAN interface. Since there is no single
+    /// interface to send on, `write` fails; use `write_to` instead.
+    pub fn open_any() -> Result<CanSocket, CanSocketOpenError> {
+        CanSocket::open_interface(0)
+    }
+
+    /// Open a socket bound to `addr`.
+    pub fn open_addr(addr: &CanAddr) -> Result<CanSocket, CanSocketOpenError> {
+        match CanSocket::open_socket() {
+            Ok(fd) => CanSocket::bind_socket(addr, fd),
+            Err(e) => Err(e),
         }
     }
 
@@ -43,10 +207,7 @@ impl CanSocket {
     ///
     /// Opens a CAN device by kernel interface number.
     fn open_interface(if_index: libc::c_uint) -> Result<CanSocket, CanSocketOpenError> {
-        match CanSocket::open_socket() {
-            Ok(fd) => CanSocket::bind_socket(if_index, fd), 
-            Err(e) => Err(e),
-        }
+        CanSocket::open_addr(&CanAddr::new(if_index))
     }
 
     fn open_socket() -> Result<i32, CanSocketOpenError> {
@@ -55,110 +216,858 @@ impl CanSocket {
             fd = libc::socket(libc::PF_CAN, libc::SOCK_RAW, CAN_RAW);
         }
 
-        if fd == -1 {
-            return Err(CanSocketOpenError::from(io::Error::last_os_error()));
+        if fd == -1 {
+            let e = io::Error::last_os_error();
+
+            return Err(match e.raw_os_error() {
+                Some(libc::EPERM) | Some(libc::EACCES) => CanSocketOpenError::PermissionDenied(e),
+                _ => CanSocketOpenError::from(e),
+            });
+        }
+
+        Ok(fd)
+    }
+
+    fn bind_socket(addr: &CanAddr, fd: i32) -> Result<CanSocket, CanSocketOpenError> {
+        // dropping the socket cleans up the resource if failure to open
+        let socket = CanSocket { fd: fd, drop_log_level: Level::Debug, timestamp_resolution: TimestampResolution::Micros };
+        socket.bind(addr)?;
+        Ok(socket)
+    }
+
+    fn bind(&self, addr: &CanAddr) -> io::Result<()> {
+        let r: i32;
+        unsafe {
+            let p = addr as *const CanAddr;
+            r = libc::bind(self.fd,
+                           p as *const libc::sockaddr,
+                           mem::size_of::<CanAddr>() as u32
+            );
+        }
+
+        if r == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Kernel interface number of the interface the socket is bound to.
+    pub fn interface_index(&self) -> io::Result<u32> {
+        self.local_addr().map(|addr| addr.if_index())
+    }
+
+    /// Address the socket is bound to, as returned by `getsockname`.
+    pub fn local_addr(&self) -> io::Result<CanAddr> {
+        let mut addr: CanAddr = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<CanAddr>() as libc::socklen_t;
+
+        let r = unsafe {
+            libc::getsockname(self.fd,
+                              &mut addr as *mut CanAddr as *mut libc::sockaddr,
+                              &mut len)
+        };
+
+        if r == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(addr)
+    }
+
+    /// Name of the interface the socket is bound to, such as "vcan0".
+    pub fn interface_name(&self) -> io::Result<String> {
+        let if_index = self.interface_index()?;
+        let mut buf = [0 as libc::c_char; libc::IF_NAMESIZE];
+
+        let r = unsafe { libc::if_indextoname(if_index, buf.as_mut_ptr()) };
+
+        if r.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        let name = unsafe { CStr::from_ptr(buf.as_ptr()) };
+        Ok(name.to_string_lossy().into_owned())
+    }
+
+    /// MTU of the interface the socket is bound to.
+    ///
+    /// `CAN_MTU` (16) for classic CAN interfaces, `CANFD_MTU` (72) for
+    /// interfaces configured for CAN FD.
+    pub fn interface_mtu(&self) -> io::Result<usize> {
+        let mut req = IfReqMtu {
+            name: [0; libc::IF_NAMESIZE],
+            mtu: 0,
+            _pad: [0; 20],
+        };
+
+        let if_index = self.interface_index()?;
+        if unsafe { libc::if_indextoname(if_index, req.name.as_mut_ptr()) }.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        let r = unsafe { libc::ioctl(self.fd, libc::SIOCGIFMTU as _, &mut req as *mut IfReqMtu) };
+
+        if r == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(req.mtu as usize)
+    }
+
+    /// Close the socket, reporting errors.
+    ///
+    /// Closing an already closed socket does nothing. Dropping the socket
+    /// afterwards will not close the file descriptor again.
+    pub fn close(&mut self) -> io::Result<()> {
+        if self.fd < 0 {
+            return Ok(());
+        }
+
+        let r: i32;
+        unsafe {
+            r = libc::close(self.fd);
+        }
+
+        // the descriptor is released even if close fails
+        self.fd = -1;
+
+        if r == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Give up ownership of the file descriptor.
+    ///
+    /// Unlike `into_raw_fd`, the returned `OwnedFd` still closes the socket
+    /// when dropped.
+    pub fn into_fd(self) -> OwnedFd {
+        unsafe { OwnedFd::from_raw_fd(self.into_raw_fd()) }
+    }
+
+    /// Set the level at which closing the socket on drop is logged.
+    ///
+    /// Defaults to `Level::Debug`. Failures are logged at least at
+    /// `Level::Warn`.
+    pub fn set_drop_log_level(&mut self, level: Level) {
+        self.drop_log_level = level;
+    }
+
+
+    /// Blocking read a single can frame with timestamp
+    ///
+    /// Note that reading a frame and retrieving the timestamp requires two
+    /// consecutive syscalls.
+    ///
+    /// The timestamp is taken from the wall clock, which jumps when the
+    /// system time is adjusted. Use `read_monotonic` to measure intervals
+    /// between frames.
+    pub fn read(&self) -> io::Result<(CanFrame, time::SystemTime)> {
+        let frame = self.read_socket()?;
+        let ts = self.socket_timestamp()?;
+
+        if log_enabled!(Level::Trace) {
+            trace!("RX (fd: {}) {:X}", self.fd, frame);
+        }
+
+        Ok((frame, ts))
+    }
+
+    /// Blocking read a single can frame with a monotonic timestamp.
+    ///
+    /// The kernel only records receive timestamps using the wall clock. The
+    /// age of the frame is determined by comparing its timestamp with the
+    /// wall clock right after reading and subtracted from the current
+    /// `Instant`. Adjustments of the system time therefore only affect frames
+    /// that were queued while the clock was adjusted.
+    pub fn read_monotonic(&self) -> io::Result<(CanFrame, time::Instant)> {
+        let (frame, ts) = self.read()?;
+
+        let now = time::Instant::now();
+        let age = time::SystemTime::now().duration_since(ts).unwrap_or_default();
+
+        Ok((frame, now.checked_sub(age).unwrap_or(now)))
+    }
+
+    fn socket_timestamp(&self) -> io::Result<time::SystemTime> {
+        match self.timestamp_resolution {
+            TimestampResolution::Micros => {
+                let mut tv = mem::MaybeUninit::<libc::timeval>::uninit();
+                let r = unsafe { libc::ioctl(self.fd, SIOCGSTAMP as libc::c_ulong, tv.as_mut_ptr()) };
+
+                if r == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                let tv = unsafe { tv.assume_init() };
+                Ok(time::UNIX_EPOCH + time::Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000))
+            }
+            TimestampResolution::Nanos => {
+                let mut ts = mem::MaybeUninit::<libc::timespec>::uninit();
+                let r = unsafe { libc::ioctl(self.fd, SIOCGSTAMPNS as libc::c_ulong, ts.as_mut_ptr()) };
+
+                if r == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                let ts = unsafe { ts.assume_init() };
+                Ok(system_time_from_timespec(ts))
+            }
+        }
+    }
+
+    /// Set the resolution of the timestamps returned by `read`.
+    ///
+    /// Defaults to `TimestampResolution::Micros`.
+    pub fn set_timestamp_resolution(&mut self, res: TimestampResolution) {
+        self.timestamp_resolution = res;
+    }
+
+    /// Blocking read a single can frame.
+    fn read_socket(&self) -> io::Result<CanFrame> {
+        let mut frame = CanFrame::empty();
+        self.read_into(&mut frame)?;
+        Ok(frame)
+    }
+
+    /// Blocking read a single can frame into `frame`.
+    ///
+    /// Allows tight receive loops to reuse a single frame instead of
+    /// constructing a new one for every read. The contents of `frame` are
+    /// unspecified if an error is returned.
+    pub fn read_into(&self, frame: &mut CanFrame) -> io::Result<()> {
+        let r = unsafe {
+            let frame_ptr = frame as *mut CanFrame;
+            libc::read(self.fd, frame_ptr as *mut libc::c_void, mem::size_of::<CanFrame>())
+        };
+
+        if r as usize != mem::size_of::<CanFrame>() {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Blocking read the raw bytes of a single frame into `buf`.
+    ///
+    /// `buf` is resized to hold the largest frame (CAN XL) and truncated to
+    /// the number of bytes read, which is returned. The length tells the
+    /// frame type apart: `CAN_MTU` (16) for classic and `CANFD_MTU` (72) for
+    /// FD frames, CAN XL frames vary in size.
+    pub fn read_raw(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        buf.resize(CANXL_HDR_SIZE + CANXL_MAX_DLEN, 0);
+
+        let r = unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+
+        if r < 0 {
+            buf.clear();
+            return Err(io::Error::last_os_error());
+        }
+
+        buf.truncate(r as usize);
+        Ok(r as usize)
+    }
+
+    /// Iterate over the frames read from this socket, decoding error frames.
+    ///
+    /// Error frames (see `set_error_mask`) are passed through
+    /// `CanError::from_frame` and yielded as `CanEvent::Error`, all other
+    /// frames as `CanEvent::Data`. Error frames that cannot be decoded are
+    /// reported as `InvalidData` errors. The iterator never ends, read errors
+    /// such as timeouts are yielded as items.
+    pub fn events<'a>(&'a self) -> CanEvents<'a> {
+        CanEvents { socket: self }
+    }
+
+    /// Blocking read the id of a single can frame, discarding its payload.
+    ///
+    /// Only the id word is copied from the kernel, but the whole frame is
+    /// consumed. The id is returned like `CanFrame::raw_id`, including the
+    /// EFF/RTR/ERR flags.
+    pub fn read_id_only(&self) -> io::Result<u32> {
+        let mut id: u32 = 0;
+
+        // the rest of the frame is discarded, CAN_RAW returns the number of
+        // bytes copied even with MSG_TRUNC, so it is not passed
+        let r = unsafe {
+            libc::recv(self.fd,
+                       &mut id as *mut u32 as *mut libc::c_void,
+                       mem::size_of::<u32>(),
+                       0)
+        };
+
+        if r == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if (r as usize) < mem::size_of::<u32>() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "short read"));
+        }
+
+        Ok(id)
+    }
+
+    /// Blocking read a single can frame along with its receive flags.
+    ///
+    /// The flags allow telling frames looped back from this socket (see
+    /// `set_recv_own_msgs`) apart from frames sent by other sockets.
+    pub fn read_with_flags(&self) -> io::Result<(CanFrame, RecvFlags)> {
+        let (frame, msg) = self.recv_frame(&mut [])?;
+
+        let flags = RecvFlags {
+            is_own: msg.msg_flags & libc::MSG_CONFIRM != 0,
+            is_local: msg.msg_flags & libc::MSG_DONTROUTE != 0,
+        };
+
+        Ok((frame, flags))
+    }
+
+    /// Blocking read a single can frame along with the number of dropped
+    /// frames.
+    ///
+    /// The returned counter is the total number of frames the kernel has
+    /// dropped on this socket due to a full receive queue. Requires the drop
+    /// monitor to be enabled using `enable_drop_monitor`, otherwise the
+    /// counter is always zero.
+    pub fn read_monitored(&self) -> io::Result<(CanFrame, u32)> {
+        let mut control = [0u64; CONTROL_LEN];
+        let (frame, msg) = self.recv_frame(&mut control)?;
+
+        if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "control messages truncated"));
+        }
+
+        // the kernel omits the message as long as nothing has been dropped
+        let drops = find_cmsg::<u32>(&msg, libc::SOL_SOCKET, libc::SO_RXQ_OVFL).unwrap_or(0);
+
+        Ok((frame, drops))
+    }
+
+    /// Blocking read of multiple can frames with timestamps.
+    ///
+    /// Reads up to `buf.len()` frames using a single `recvmmsg` call,
+    /// blocking until at least one frame is available. Each frame carries
+    /// the kernel's receive timestamp, avoiding the extra syscall per frame
+    /// done by `read`. Returns the number of frames read into `buf`.
+    ///
+    /// Requires timestamps to be enabled using `enable_timestamps`, otherwise
+    /// this fails with `InvalidData`.
+    pub fn read_frames_timestamped(&self,
+                                   buf: &mut [(CanFrame, time::SystemTime)])
+                                   -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut iovs: Vec<libc::iovec> = buf.iter_mut()
+            .map(|&mut (ref mut frame, _)| libc::iovec {
+                iov_base: frame as *mut CanFrame as *mut libc::c_void,
+                iov_len: mem::size_of::<CanFrame>(),
+            })
+            .collect();
+        let mut controls = vec![[0u64; CONTROL_LEN]; buf.len()];
+
+        let mut msgs: Vec<libc::mmsghdr> = iovs.iter_mut()
+            .zip(controls.iter_mut())
+            .map(|(iov, control)| {
+                let mut msg: libc::mmsghdr = unsafe { mem::zeroed() };
+                msg.msg_hdr.msg_iov = iov;
+                msg.msg_hdr.msg_iovlen = 1;
+                msg.msg_hdr.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+                msg.msg_hdr.msg_controllen = mem::size_of_val(control) as _;
+                msg
+            })
+            .collect();
+
+        let r = unsafe {
+            libc::recvmmsg(self.fd,
+                           msgs.as_mut_ptr(),
+                           msgs.len() as libc::c_uint,
+                           libc::MSG_WAITFORONE as _,
+                           ptr::null_mut())
+        };
+
+        if r == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        for (msg, entry) in msgs.iter().zip(buf.iter_mut()).take(r as usize) {
+            if msg.msg_len as usize != mem::size_of::<CanFrame>() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "incomplete CAN frame"));
+            }
+
+            let ts = find_cmsg::<libc::timespec>(&msg.msg_hdr,
+                                                 libc::SOL_SOCKET,
+                                                 libc::SCM_TIMESTAMPNS)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing timestamp"))?;
+
+            entry.1 = system_time_from_timespec(ts);
+        }
+
+        Ok(r as usize)
+    }
+
+    /// Blocking read a single can frame using `recvmsg`.
+    ///
+    /// Ancillary data is received into `control`, which the returned `msghdr`
+    /// points to for walking the control messages.
+    fn recv_frame(&self, control: &mut [u64]) -> io::Result<(CanFrame, libc::msghdr)> {
+        let mut frame = CanFrame::empty();
+        let mut iov = libc::iovec {
+            iov_base: &mut frame as *mut CanFrame as *mut libc::c_void,
+            iov_len: mem::size_of::<CanFrame>(),
+        };
+
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+
+        if !control.is_empty() {
+            msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = mem::size_of_val(control) as _;
+        }
+
+        let r = unsafe { libc::recvmsg(self.fd, &mut msg, 0) };
+
+        if r as usize != mem::size_of::<CanFrame>() {
+            return Err(io::Error::last_os_error());
+        }
+
+        // iov does not outlive this function
+        msg.msg_iov = ptr::null_mut();
+        msg.msg_iovlen = 0;
+
+        Ok((frame, msg))
+    }
+
+    /// Write a single can frame.
+    ///
+    /// Note that this function can fail with an `EAGAIN` error or similar.
+    /// Use `write_insist` if you need to be sure that the message got
+    /// sent or failed.
+    ///
+    /// Error frames are receive-only, attempting to write a frame with the
+    /// error flag set fails with `InvalidInput`.
+    ///
+    /// If the interface is down or has been removed, the error can be
+    /// recognized using `IsInterfaceDown::is_interface_down`.
+    pub fn write(&self, frame: &CanFrame) -> io::Result<()> {
+        if frame.is_error() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "error frames cannot be transmitted"));
+        }
+
+        let n = self.write_frame_raw(frame)?;
+
+        if n != mem::size_of::<CanFrame>() {
+            return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                      format!("partial write of {} bytes", n)));
+        }
+
+        if log_enabled!(Level::Trace) {
+            trace!("TX (fd: {}) {:X}", self.fd, frame);
+        }
+
+        Ok(())
+    }
+
+    /// Write a single can frame, returning the number of bytes written.
+    ///
+    /// A CAN socket always writes whole frames, so any count other than
+    /// `CAN_MTU` (16) indicates a problem with the socket. Unlike `write`,
+    /// the frame is passed to the kernel unchecked and a short write is not
+    /// treated as an error, which helps diagnosing transport issues.
+    pub fn write_frame_raw(&self, frame: &CanFrame) -> io::Result<usize> {
+        let r = unsafe {
+            let frame_ptr = frame as *const CanFrame;
+            libc::write(self.fd, frame_ptr as *const libc::c_void, mem::size_of::<CanFrame>())
+        };
+
+        if r < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(r as usize)
+    }
+
+    /// Blocking write a single can frame, retrying until it gets sent
+    /// successfully.
+    pub fn write_insist(&self, frame: &CanFrame) -> io::Result<()> {
+        loop {
+            match self.write(frame) {
+                Ok(v) => return Ok(v),
+                Err(e) => {
+                    if !e.should_retry() {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write a single can frame, retrying until it is sent or `deadline`
+    /// passes.
+    ///
+    /// Returns `Ok(true)` once the frame was sent and `Ok(false)` if it could
+    /// not be sent in time. Errors that do not indicate a timeout are
+    /// returned immediately. Between attempts the socket is polled for
+    /// writability rather than spinning; a full TX queue (`ENOBUFS`) is
+    /// retried after a short pause.
+    pub fn write_insist_deadline(&self, frame: &CanFrame, deadline: time::Instant) -> io::Result<bool> {
+        loop {
+            let queue_full = match self.write(frame) {
+                Ok(()) => return Ok(true),
+                Err(ref e) if is_tx_queue_full(e) => true,
+                Err(e) => {
+                    if !e.should_retry() {
+                        return Err(e);
+                    }
+                    false
+                }
+            };
+
+            let now = time::Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+
+            if queue_full {
+                thread::sleep(cmp::min(TX_QUEUE_FULL_BACKOFF, deadline - now));
+            } else {
+                poll_fd(self.fd, libc::POLLOUT, deadline - now)?;
+            }
+        }
+    }
+
+    /// Write a single can frame, waiting at most `timeout` for the socket to
+    /// become writable.
+    ///
+    /// Returns `Ok(false)` if the frame could not be sent in time. Unlike
+    /// `set_write_timeout`, no socket option is changed: the frame is sent
+    /// with `MSG_DONTWAIT` and the socket is polled in between attempts, so
+    /// this works the same in blocking and non-blocking mode. A full TX
+    /// queue (`ENOBUFS`) is retried after a short pause until the timeout.
+    pub fn write_timeout(&self, frame: &CanFrame, timeout: time::Duration) -> io::Result<bool> {
+        if frame.is_error() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "error frames cannot be transmitted"));
+        }
+
+        let deadline = time::Instant::now() + timeout;
+
+        loop {
+            let r = unsafe {
+                libc::send(self.fd,
+                           frame as *const CanFrame as *const libc::c_void,
+                           mem::size_of::<CanFrame>(),
+                           libc::MSG_DONTWAIT)
+            };
+
+            if r as usize == mem::size_of::<CanFrame>() {
+                if log_enabled!(Level::Trace) {
+                    trace!("TX (fd: {}) {:X}", self.fd, frame);
+                }
+                return Ok(true);
+            }
+
+            let e = io::Error::last_os_error();
+            let queue_full = is_tx_queue_full(&e);
+            if !queue_full && !e.should_retry() {
+                return Err(e);
+            }
+
+            let now = time::Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+
+            if queue_full {
+                thread::sleep(cmp::min(TX_QUEUE_FULL_BACKOFF, deadline - now));
+            } else {
+                poll_fd(self.fd, libc::POLLOUT, deadline - now)?;
+            }
+        }
+    }
+
+    /// Write a single can frame to the interface `if_index`.
+    ///
+    /// Allows sockets bound to all interfaces (see `open_any`) to transmit,
+    /// by passing the target interface to `sendto`.
+    pub fn write_to(&self, frame: &CanFrame, if_index: u32) -> io::Result<()> {
+        if frame.is_error() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "error frames cannot be transmitted"));
+        }
+
+        let addr = CanAddr::new(if_index);
+
+        let r = unsafe {
+            libc::sendto(self.fd,
+                         frame as *const CanFrame as *const libc::c_void,
+                         mem::size_of::<CanFrame>(),
+                         0,
+                         &addr as *const CanAddr as *const libc::sockaddr,
+                         mem::size_of::<CanAddr>() as libc::socklen_t)
+        };
+
+        if r as usize != mem::size_of::<CanFrame>() {
+            return Err(io::Error::last_os_error());
+        }
+
+        if log_enabled!(Level::Trace) {
+            trace!("TX (fd: {}, if: {}) {:X}", self.fd, if_index, frame);
+        }
+
+        Ok(())
+    }
+
+    /// Write `count` copies of a frame as fast as the kernel accepts them.
+    ///
+    /// Frames are handed to the kernel in batches using `sendmmsg`. Returns
+    /// the number of frames written, which is less than `count` only if a
+    /// non-blocking socket would have blocked. Other errors are returned
+    /// as-is, frames sent before the error are not reported.
+    pub fn write_burst(&self, frame: &CanFrame, count: usize) -> io::Result<usize> {
+        const BATCH: usize = 64;
+
+        if frame.is_error() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "error frames cannot be transmitted"));
+        }
+
+        let mut iov = libc::iovec {
+            iov_base: frame as *const CanFrame as *mut libc::c_void,
+            iov_len: mem::size_of::<CanFrame>(),
+        };
+        let mut msgs: [libc::mmsghdr; BATCH] = unsafe { mem::zeroed() };
+        for msg in msgs.iter_mut() {
+            msg.msg_hdr.msg_iov = &mut iov;
+            msg.msg_hdr.msg_iovlen = 1;
+        }
+
+        let mut sent = 0;
+        while sent < count {
+            let batch = cmp::min(count - sent, BATCH);
+            let r = unsafe { libc::sendmmsg(self.fd, msgs.as_mut_ptr(), batch as libc::c_uint, 0) };
+
+            if r < 0 {
+                let e = io::Error::last_os_error();
+                if e.should_retry() {
+                    break;
+                }
+                return Err(e);
+            }
+            sent += r as usize;
+        }
+
+        if log_enabled!(Level::Trace) {
+            trace!("TX (fd: {}) {} x {:X}", self.fd, sent, frame);
+        }
+
+        Ok(sent)
+    }
+
+    /// Write a single can frame without automatic retransmission.
+    ///
+    /// The kernel has no per-frame single-shot flag, retransmission is
+    /// disabled for the whole interface by enabling the controller's
+    /// one-shot mode (`ip link set can0 type can one-shot on`). This method
+    /// checks that the interface is in one-shot mode before writing and fails
+    /// with `Unsupported` otherwise, rather than silently falling back to
+    /// retransmitting frames. Use `write` if retransmission is acceptable.
+    pub fn write_once(&self, frame: &CanFrame) -> io::Result<()> {
+        if can_ctrlmode(self.interface_index()?)? & CAN_CTRLMODE_ONE_SHOT == 0 {
+            return Err(io::Error::new(io::ErrorKind::Unsupported,
+                                      "interface is not in one-shot mode"));
+        }
+
+        self.write(frame)
+    }
+
+    /// Write a single can frame without looping it back to other sockets.
+    ///
+    /// The kernel offers no per-message loopback control, so loopback is
+    /// disabled for the duration of the write and the previous setting is
+    /// restored afterwards. Frames written concurrently from other threads
+    /// through this socket are affected as well.
+    pub fn write_no_loopback(&self, frame: &CanFrame) -> io::Result<()> {
+        let loopback: libc::c_int = get_socket_option(self.fd, SOL_CAN_RAW, CAN_RAW_LOOPBACK)?;
+        if loopback == 0 {
+            return self.write(frame);
+        }
+
+        self.set_loopback(false)?;
+        let r = self.write(frame);
+        self.set_loopback(true)?;
+        r
+    }
+
+    /// Write a single can frame and wait for it to be echoed back.
+    ///
+    /// The frame is written through a second socket bound to the same
+    /// interface, which receives its own messages. Waits up to `timeout` for
+    /// the kernel to loop the frame back, confirming that it has been sent.
+    /// Returns `Ok(false)` if no echo arrived in time.
+    ///
+    /// No option of this socket is changed and nothing is read from it, so
+    /// readers on other threads are not affected. Like any frame written by
+    /// another local socket, the frame is received by this socket if it
+    /// matches its filters. Requires the socket to be bound to a single
+    /// interface.
+    pub fn write_and_confirm(&self, frame: &CanFrame, timeout: time::Duration) -> io::Result<bool> {
+        let tx = CanSocket::open_interface(self.interface_index()?).map_err(|e| match e {
+            CanSocketOpenError::IOError(e) | CanSocketOpenError::PermissionDenied(e) => e,
+            e => io::Error::other(e.to_string()),
+        })?;
+
+        // only the echo is of interest
+        tx.set_filters(&[CanFilter::new(frame.raw_id(), EFF_MASK | EFF_FLAG | RTR_FLAG)
+                             .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?])?;
+        tx.set_recv_own_msgs(true)?;
+        tx.write(frame)?;
+
+        let deadline = time::Instant::now() + timeout;
+
+        loop {
+            let now = time::Instant::now();
+            if now >= deadline || !poll_fd(tx.fd, libc::POLLIN, deadline - now)? {
+                return Ok(false);
+            }
+
+            let (rx, flags) = tx.read_with_flags()?;
+            if flags.is_own && rx.raw_id() == frame.raw_id() && rx.data() == frame.data() {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Read the timestamp of a transmitted frame from the error queue.
+    ///
+    /// Requires TX timestamps to be enabled using `enable_tx_timestamps`.
+    /// Timestamps are queued in the order frames were sent. This call never
+    /// blocks and fails with `WouldBlock` if no timestamp is queued; the
+    /// socket polls as `POLLERR` once one is available.
+    pub fn recv_tx_timestamp(&self) -> io::Result<time::SystemTime> {
+        let mut control = [0u64; CONTROL_LEN];
+        let mut buf = [0u8; CANFD_MTU];
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = mem::size_of_val(&control) as _;
+
+        let r = unsafe { libc::recvmsg(self.fd, &mut msg, libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT) };
+
+        if r == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // the first of the three timestamps holds the software timestamp
+        match find_cmsg::<[libc::timespec; 3]>(&msg, libc::SOL_SOCKET, libc::SCM_TIMESTAMPING) {
+            Some(ts) => Ok(system_time_from_timespec(ts[0])),
+            None => Err(io::Error::new(io::ErrorKind::InvalidData, "no TX timestamp received")),
         }
-
-        Ok(fd)
     }
 
-    fn bind_socket(if_index: libc::c_uint, fd: i32) -> Result<CanSocket, CanSocketOpenError> { 
-        let socketaddr = CanAddr {
-            af_can: libc::AF_CAN as libc::c_short,
-            if_index: if_index as libc::c_int,
-            rx_id: 0,
-            tx_id: 0,
+    /// Blocking read a single classic or CAN FD frame.
+    ///
+    /// Requires FD frames to be enabled using `enable_fd_frames`, otherwise
+    /// only classic frames are received. The frame type is determined by the
+    /// number of bytes returned by the kernel. If XL frames are enabled as
+    /// well, receiving an XL frame fails with `InvalidData`, use `read_xl`
+    /// for those.
+    pub fn read_any(&self) -> io::Result<AnyFrame> {
+        // room for an XL frame, so one is not truncated to the size of an FD
+        // frame and mistaken for it
+        let mut frame = CanXlFrame::empty();
+
+        let r = unsafe {
+            let frame_ptr = &mut frame as *mut CanXlFrame;
+            libc::read(self.fd, frame_ptr as *mut libc::c_void, mem::size_of::<CanXlFrame>())
         };
 
-        let r: i32;
-        unsafe {
-            let p = &socketaddr as *const CanAddr;
-            r = libc::bind(fd,
-                           p as *const libc::sockaddr,
-                           mem::size_of::<CanAddr>() as u32
-            );
+        if r < 0 {
+            return Err(io::Error::last_os_error());
         }
 
-        if r == -1 {
-            let e = io::Error::last_os_error();
-            // clean up resource if failure to open
-            unsafe { libc::close(fd); }
-            return Err(CanSocketOpenError::from(e));
+        // XL frames may have the same size as classic or FD frames
+        if frame.is_xl() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected CAN XL frame"));
         }
-        
-        Ok(CanSocket { fd: fd })
-    }
 
-    pub fn close(&mut self) -> io::Result<()> {
-        let r: i32;
-        unsafe {
-            r = libc::close(self.fd);
-        }
+        // the buffer is large enough and aligned for a struct canfd_frame
+        let fd = unsafe { ptr::read(&frame as *const CanXlFrame as *const CanFdFrame) };
 
-        if r == -1 {
-            return Err(io::Error::last_os_error());
+        match r as usize {
+            CAN_MTU => Ok(AnyFrame::Classic(fd.as_classic())),
+            CANFD_MTU => Ok(AnyFrame::Fd(fd)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected frame size")),
         }
-
-        Ok(())
     }
 
-
-    /// Blocking read a single can frame with timestamp
+    /// Write a single CAN FD frame.
     ///
-    /// Note that reading a frame and retrieving the timestamp requires two
-    /// consecutive syscalls.
-    pub fn read(&self) -> io::Result<(CanFrame, time::SystemTime)> {
-        let frame = self.read_socket()?;
-        let ts = self.socket_timestamp()?;
-
-        Ok((frame, ts))
-    }
+    /// Requires FD frames to be enabled using `enable_fd_frames`.
+    pub fn write_fd(&self, frame: &CanFdFrame) -> io::Result<()> {
+        if frame.is_error() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "error frames cannot be transmitted"));
+        }
 
-    fn socket_timestamp(&self) -> io::Result<time::SystemTime> {
-        let mut ts = mem::MaybeUninit::<libc::timespec>::uninit();
-        let r = unsafe { 
-            libc::ioctl(self.fd,
-                        SIOCGSTAMP as libc::c_ulong,
-                        ts.as_mut_ptr())
+        let r = unsafe {
+            let frame_ptr = frame as *const CanFdFrame;
+            libc::write(self.fd, frame_ptr as *const libc::c_void, mem::size_of::<CanFdFrame>())
         };
 
-        if r == -1 {
+        if r as usize != mem::size_of::<CanFdFrame>() {
             return Err(io::Error::last_os_error());
         }
 
-        let ts = unsafe { ts.assume_init() };
-        
-        Ok(system_time_from_timespec(ts))
+        Ok(())
     }
-    
-    /// Blocking read a single can frame.
-    fn read_socket(&self) -> io::Result<CanFrame> {
-        let mut frame = CanFrame::empty();
+
+    /// Blocking read a single CAN XL frame.
+    ///
+    /// Requires XL frames to be enabled using `enable_xl_frames`. Since an
+    /// XL-enabled socket receives classic and FD frames as well, receiving a
+    /// frame that is not an XL frame fails with `InvalidData`.
+    pub fn read_xl(&self) -> io::Result<CanXlFrame> {
+        let mut frame = CanXlFrame::empty();
 
         let r = unsafe {
-            let frame_ptr = &mut frame as *mut CanFrame;
-            libc::read(self.fd, frame_ptr as *mut libc::c_void, mem::size_of::<CanFrame>())
+            let frame_ptr = &mut frame as *mut CanXlFrame;
+            libc::read(self.fd, frame_ptr as *mut libc::c_void, mem::size_of::<CanXlFrame>())
         };
 
-        if r as usize != mem::size_of::<CanFrame>() {
+        if r < 0 {
             return Err(io::Error::last_os_error());
         }
 
+        if !frame.is_xl() || r as usize != frame.wire_len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "received frame is not a CAN XL frame"));
+        }
+
         Ok(frame)
     }
 
-    /// Write a single can frame.
+    /// Write a single CAN XL frame.
     ///
-    /// Note that this function can fail with an `EAGAIN` error or similar.
-    /// Use `write_frame_insist` if you need to be sure that the message got
-    /// sent or failed.
-    pub fn write(&self, frame: &CanFrame) -> io::Result<()> {
+    /// Requires XL frames to be enabled using `enable_xl_frames`.
+    pub fn write_xl(&self, frame: &CanXlFrame) -> io::Result<()> {
         let r = unsafe {
-            let frame_ptr = frame as *const CanFrame;
-            libc::write(self.fd, frame_ptr as *const libc::c_void, mem::size_of::<CanFrame>())
+            let frame_ptr = frame as *const CanXlFrame;
+            libc::write(self.fd, frame_ptr as *const libc::c_void, frame.wire_len())
         };
 
-        if r as usize != mem::size_of::<CanFrame>() {
+        if r as usize != frame.wire_len() {
             return Err(io::Error::last_os_error());
         }
 
@@ -188,6 +1097,68 @@ impl CanSocket {
         Ok(())
     }
 
+    /// Check whether the socket is in non-blocking mode.
+    pub fn is_nonblocking(&self) -> io::Result<bool> {
+        let flags = unsafe { libc::fcntl(self.fd, libc::F_GETFL) };
+
+        if flags == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(flags & libc::O_NONBLOCK != 0)
+    }
+
+    /// Discard all frames currently queued for reading.
+    ///
+    /// Reads frames in non-blocking mode until none are left, returning the
+    /// number of frames discarded. The previous blocking mode of the socket
+    /// is restored afterwards.
+    pub fn drain(&self) -> io::Result<usize> {
+        let nonblocking = self.is_nonblocking()?;
+        self.set_nonblocking(true)?;
+
+        let mut count = 0;
+        let r = loop {
+            match self.read_socket() {
+                Ok(_) => count += 1,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(count),
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.set_nonblocking(nonblocking)?;
+        r
+    }
+
+    /// Read all frames currently queued, without blocking.
+    ///
+    /// Frames are received with `MSG_DONTWAIT` until none are left, so the
+    /// socket's blocking mode is left untouched. Returns an empty `Vec` if no
+    /// frames are queued. Errors other than `WouldBlock` are returned.
+    pub fn read_available(&self) -> io::Result<Vec<CanFrame>> {
+        let mut frames = Vec::new();
+
+        loop {
+            let mut frame = CanFrame::empty();
+            let r = unsafe {
+                libc::recv(self.fd,
+                           &mut frame as *mut CanFrame as *mut libc::c_void,
+                           mem::size_of::<CanFrame>(),
+                           libc::MSG_DONTWAIT)
+            };
+
+            if r as usize != mem::size_of::<CanFrame>() {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::WouldBlock {
+                    return Ok(frames);
+                }
+                return Err(e);
+            }
+
+            frames.push(frame);
+        }
+    }
+
     /// Set the read timeout on the socket
     ///
     /// For convenience, the result value can be checked using
@@ -218,10 +1189,52 @@ impl CanSocket {
     ///
     /// See `CanFilter` for details on how filtering works. By default, all
     /// single filter matching all incoming frames is installed.
+    ///
+    /// Note that an empty list of filters causes the socket to receive no
+    /// frames at all, see `filter_drop_all` and `filter_accept_all`.
+    ///
+    /// The kernel accepts at most `CAN_RAW_FILTER_MAX` (512) filters, longer
+    /// lists fail with `InvalidInput`. `CanFilter::optimize` may help to
+    /// shorten the list.
     pub fn set_filters(&self, filters: &[CanFilter]) -> io::Result<()> {
+        if filters.len() > CAN_RAW_FILTER_MAX {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("too many filters (got {}, max {})",
+                                              filters.len(),
+                                              CAN_RAW_FILTER_MAX)));
+        }
+
         set_socket_option_mult(self.fd, SOL_CAN_RAW, CAN_RAW_FILTER, filters)
     }
 
+    /// Disable reception of all frames.
+    ///
+    /// Installs an empty filter list, which the kernel treats as "receive
+    /// nothing". Error frames are still received if enabled through
+    /// `set_error_mask`.
+    pub fn filter_drop_all(&self) -> io::Result<()> {
+        self.set_filters(&[])
+    }
+
+    /// Enable reception of all frames.
+    ///
+    /// Installs a single filter with id and mask 0, matching every frame.
+    /// This is the default for a newly opened socket.
+    pub fn filter_accept_all(&self) -> io::Result<()> {
+        self.set_filters(&[CanFilter::default()])
+    }
+
+    /// Sets filters on the socket from an iterator.
+    ///
+    /// Same as `set_filters`, but accepts any iterator of filters. An empty
+    /// iterator is handled just like an empty slice.
+    pub fn set_filters_iter<I>(&self, filters: I) -> io::Result<()>
+        where I: IntoIterator<Item = CanFilter>
+    {
+        let filters: Vec<CanFilter> = filters.into_iter().collect();
+        self.set_filters(&filters)
+    }
+
     /// Sets the error mask on the socket.
     ///
     /// By default (`ERR_MASK_NONE`) no error conditions are reported as
@@ -233,11 +1246,25 @@ impl CanSocket {
         set_socket_option(self.fd, SOL_CAN_RAW, CAN_RAW_ERR_FILTER, &mask)
     }
 
+    /// The error mask currently set on the socket, see `set_error_mask`.
+    pub fn error_mask(&self) -> io::Result<u32> {
+        get_socket_option(self.fd, SOL_CAN_RAW, CAN_RAW_ERR_FILTER)
+    }
+
+    /// Check if any error conditions are reported as error frames.
+    pub fn error_reporting_enabled(&self) -> io::Result<bool> {
+        Ok(self.error_mask()? != ERR_MASK_NONE)
+    }
+
     /// Enable or disable loopback.
     ///
     /// By default, loopback is enabled, causing other applications that open
     /// the same CAN bus to see frames emitted by different applications on
-    /// the same system.
+    /// the same system. With loopback disabled, frames written to this socket
+    /// are only sent to the bus, and `set_recv_own_msgs` has no effect.
+    ///
+    /// The option only affects frames written after it is set. To disable
+    /// loopback from the first frame on, use `CanSocketBuilder::loopback`.
     #[inline]
     pub fn set_loopback(&self, enabled: bool) -> io::Result<()> {
         let loopback: libc::c_int = match enabled {
@@ -259,6 +1286,115 @@ impl CanSocket {
         set_socket_option(self.fd, SOL_CAN_RAW, CAN_RAW_RECV_OWN_MSGS, &recv_own_msgs)
     }
 
+    /// Enable sending and receiving of CAN FD frames.
+    ///
+    /// Once enabled, the socket receives CAN FD frames in addition to classic
+    /// frames, use `read_any` to receive both.
+    ///
+    /// The kernel accepts the option even if the interface is not configured
+    /// for CAN FD, so the interface MTU is checked first. Enabling fails with
+    /// `Unsupported` unless it is at least `CANFD_MTU` (72). The check is
+    /// skipped for sockets bound to all interfaces. Kernels without CAN FD
+    /// support reject the option with `ENOPROTOOPT`, which is also reported
+    /// as `Unsupported`.
+    pub fn enable_fd_frames(&self) -> io::Result<()> {
+        if self.interface_index()? != 0 {
+            let mtu = self.interface_mtu()?;
+
+            if mtu < CANFD_MTU {
+                return Err(io::Error::new(io::ErrorKind::Unsupported,
+                                          format!("interface does not support CAN FD (MTU {})",
+                                                  mtu)));
+            }
+        }
+
+        let fd_frames: libc::c_int = 1;
+        set_socket_option(self.fd, SOL_CAN_RAW, CAN_RAW_FD_FRAMES, &fd_frames).map_err(|e| {
+            if e.raw_os_error() == Some(libc::ENOPROTOOPT) {
+                io::Error::new(io::ErrorKind::Unsupported, "kernel does not support CAN FD frames")
+            } else {
+                e
+            }
+        })
+    }
+
+    /// Enable CAN FD frames if supported by kernel and interface.
+    ///
+    /// Returns `Ok(false)` instead of failing if `enable_fd_frames` reports
+    /// `Unsupported`, allowing applications to fall back to classic frames.
+    pub fn try_enable_fd_frames(&self) -> io::Result<bool> {
+        match self.enable_fd_frames() {
+            Ok(()) => Ok(true),
+            Err(ref e) if e.kind() == io::ErrorKind::Unsupported => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Enable sending and receiving of CAN XL frames.
+    ///
+    /// Once enabled, the socket receives CAN XL frames in addition to
+    /// classic (and FD) frames. The interface must support CAN XL.
+    pub fn enable_xl_frames(&self) -> io::Result<()> {
+        let xl_frames: libc::c_int = 1;
+        set_socket_option(self.fd, SOL_CAN_RAW, CAN_RAW_XL_FRAMES, &xl_frames)
+    }
+
+    /// Enable reporting of dropped frames.
+    ///
+    /// Sets `SO_RXQ_OVFL`, causing the kernel to report the number of frames
+    /// dropped due to receive buffer overflow. Use `read_monitored` to
+    /// retrieve the counter along with each frame.
+    pub fn enable_drop_monitor(&self) -> io::Result<()> {
+        let rxq_ovfl: libc::c_int = 1;
+        set_socket_option(self.fd, libc::SOL_SOCKET, libc::SO_RXQ_OVFL, &rxq_ovfl)
+    }
+
+    /// Enable receive timestamps attached to every frame.
+    ///
+    /// Sets `SO_TIMESTAMPNS`, required by `read_frames_timestamped`. Every
+    /// frame received from then on carries its timestamp as a control
+    /// message, `read` is not affected.
+    pub fn enable_timestamps(&self) -> io::Result<()> {
+        let timestamps: libc::c_int = 1;
+        set_socket_option(self.fd, libc::SOL_SOCKET, libc::SO_TIMESTAMPNS, &timestamps)
+    }
+
+    /// Enable software timestamps of transmitted frames.
+    ///
+    /// Sets `SO_TIMESTAMPING`, causing the kernel to queue a timestamp for
+    /// every frame handed to the driver. Use `recv_tx_timestamp` to retrieve
+    /// them.
+    ///
+    /// A queued timestamp makes the socket poll as `POLLERR` without
+    /// `POLLIN`, so code polling the socket must only read on `POLLIN`.
+    /// Once receive timestamps are generated, e.g. after `enable_timestamps`,
+    /// received frames also carry an `SCM_TIMESTAMPING` control message.
+    pub fn enable_tx_timestamps(&self) -> io::Result<()> {
+        let flags = (libc::SOF_TIMESTAMPING_TX_SOFTWARE |
+                     libc::SOF_TIMESTAMPING_SOFTWARE |
+                     libc::SOF_TIMESTAMPING_OPT_TSONLY) as libc::c_int;
+        set_socket_option(self.fd, libc::SOL_SOCKET, libc::SO_TIMESTAMPING, &flags)
+    }
+
+    /// Set the mark of the socket (`SO_MARK`).
+    ///
+    /// Frames sent through the socket carry the mark, allowing them to be
+    /// classified by traffic control or firewall rules. Requires the
+    /// `CAP_NET_ADMIN` capability.
+    pub fn set_mark(&self, mark: u32) -> io::Result<()> {
+        set_socket_option(self.fd, libc::SOL_SOCKET, libc::SO_MARK, &mark)
+    }
+
+    /// Set the priority of frames sent through the socket (`SO_PRIORITY`).
+    ///
+    /// The priority selects the band of a priority aware queueing discipline
+    /// configured on the interface, e.g. `pfifo_fast` or `prio`, letting
+    /// control traffic overtake bulk traffic. Priorities outside 0 to 6
+    /// require the `CAP_NET_ADMIN` capability.
+    pub fn set_priority(&self, prio: i32) -> io::Result<()> {
+        set_socket_option(self.fd, libc::SOL_SOCKET, libc::SO_PRIORITY, &(prio as libc::c_int))
+    }
+
     /// Enable or disable join filters.
     ///
     /// By default a frame is accepted if it matches any of the filters set
@@ -281,21 +1417,405 @@ impl AsRawFd for CanSocket {
 
 impl FromRawFd for CanSocket {
     unsafe fn from_raw_fd(fd: RawFd) -> CanSocket {
-        CanSocket { fd: fd }
+        CanSocket { fd: fd, drop_log_level: Level::Debug, timestamp_resolution: TimestampResolution::Micros }
     }
 }
 
 impl IntoRawFd for CanSocket {
     fn into_raw_fd(self) -> RawFd {
-        self.fd
+        let fd = self.fd;
+        // the caller owns the descriptor now, do not close it
+        mem::forget(self);
+        fd
     }
 }
 
 impl Drop for CanSocket {
     fn drop(&mut self) {
+        if self.fd < 0 {
+            return;
+        }
+
+        let fd = self.fd;
         match self.close() {
-            Ok(_) => debug!("Socket dropped (fd: {})", self.fd),
-            Err(e) => debug!("Error dropping socket {}", e),
+            Ok(_) => log!(self.drop_log_level, "Socket dropped (fd: {})", fd),
+            Err(e) => {
+                log!(cmp::min(self.drop_log_level, Level::Warn), "Error dropping socket (fd: {}): {}", fd, e)
+            }
+        };
+    }
+}
+
+/// List the names of all CAN network interfaces, such as "can0" or "vcan0".
+///
+/// Reads the interface types from `/sys/class/net`, interfaces that are down
+/// are included. The names are sorted.
+pub fn available_interfaces() -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+
+    for entry in fs::read_dir("/sys/class/net")? {
+        let entry = entry?;
+
+        // interfaces may vanish while iterating
+        let ty = match fs::read_to_string(entry.path().join("type")) {
+            Ok(ty) => ty,
+            Err(_) => continue,
         };
+
+        if ty.trim().parse() == Ok(ARPHRD_CAN) {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+
+    names.sort();
+    Ok(names)
+}
+
+/// Send recorded frames, reproducing their original timing.
+///
+/// Each item holds the offset from the previous frame and the frame itself.
+/// Send times are computed from a monotonic clock relative to the start of
+/// the replay, so delays caused by writing do not accumulate.
+pub fn replay<I>(socket: &CanSocket, frames: I) -> io::Result<()>
+    where I: IntoIterator<Item = (time::Duration, CanFrame)>
+{
+    let mut next = time::Instant::now();
+
+    for (delta, frame) in frames {
+        next += delta;
+
+        let now = time::Instant::now();
+        if next > now {
+            thread::sleep(next - now);
+        }
+
+        socket.write(&frame)?;
+    }
+
+    Ok(())
+}
+
+/// A CAN socket enforcing a minimum interval between transmissions.
+///
+/// Wraps a `CanSocket`, `write` sleeps if the previous frame was sent less
+/// than `min_interval` ago. Useful to avoid flooding the bus.
+#[derive(Debug)]
+pub struct PacedCanSocket {
+    socket: CanSocket,
+    min_interval: time::Duration,
+    last_write: Option<time::Instant>,
+}
+
+impl PacedCanSocket {
+    /// Wrap a socket, spacing writes at least `min_interval` apart.
+    pub fn new(socket: CanSocket, min_interval: time::Duration) -> PacedCanSocket {
+        PacedCanSocket {
+            socket,
+            min_interval,
+            last_write: None,
+        }
+    }
+
+    /// Write a single can frame, waiting for the minimum interval to pass
+    /// first if necessary.
+    pub fn write(&mut self, frame: &CanFrame) -> io::Result<()> {
+        if let Some(last_write) = self.last_write {
+            let elapsed = last_write.elapsed();
+
+            if elapsed < self.min_interval {
+                thread::sleep(self.min_interval - elapsed);
+            }
+        }
+
+        self.socket.write(frame)?;
+        self.last_write = Some(time::Instant::now());
+
+        Ok(())
+    }
+
+    /// Access the underlying socket, e.g. for reading or configuration.
+    pub fn get_ref(&self) -> &CanSocket {
+        &self.socket
+    }
+
+    /// Unwrap the underlying socket.
+    pub fn into_inner(self) -> CanSocket {
+        self.socket
+    }
+}
+
+/// A CAN interface that hands out independent sockets.
+///
+/// Every socket opened on an interface receives all frames on the bus, so
+/// multiple consumers can each `subscribe` with their own kernel-side
+/// filters instead of reopening the interface by name.
+#[derive(Debug, Copy, Clone)]
+pub struct CanBus {
+    if_index: libc::c_uint,
+}
+
+impl CanBus {
+    /// Look up a named CAN device, such as "vcan0".
+    pub fn open(ifname: &str) -> Result<CanBus, CanSocketOpenError> {
+        let if_index = if_nametoindex(ifname)?;
+        Ok(CanBus { if_index })
+    }
+
+    /// Kernel interface number of the bus.
+    pub fn interface_index(&self) -> u32 {
+        self.if_index
+    }
+
+    /// Open a new socket on the bus receiving only frames matching `filters`.
+    ///
+    /// As with `CanSocket::set_filters`, an empty filter list receives no
+    /// frames at all. Use `CanFilter::default()` to receive everything.
+    pub fn subscribe(&self, filters: &[CanFilter]) -> Result<CanSocket, CanSocketOpenError> {
+        let socket = CanSocket::open_interface(self.if_index)?;
+        socket.set_filters(filters)?;
+        Ok(socket)
+    }
+}
+
+/// Builder configuring a `CanSocket` before it is bound to an interface.
+///
+/// Options left unset keep the kernel defaults. All options are applied
+/// before binding, so no frames are received with an intermediate
+/// configuration.
+///
+/// ```no_run
+/// use socketcan::filter::CanFilter;
+/// use socketcan::socket::CanSocketBuilder;
+///
+/// let socket = CanSocketBuilder::new()
+///     .loopback(false)
+///     .filters(&[CanFilter::new(0x100, 0x700).unwrap()])
+///     .open("vcan0")
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CanSocketBuilder {
+    loopback: Option<bool>,
+    recv_own_msgs: Option<bool>,
+    nonblocking: Option<bool>,
+    filters: Option<Vec<CanFilter>>,
+    error_mask: Option<u32>,
+    read_timeout: Option<time::Duration>,
+}
+
+impl CanSocketBuilder {
+    pub fn new() -> CanSocketBuilder {
+        CanSocketBuilder::default()
+    }
+
+    /// See `CanSocket::set_loopback`.
+    ///
+    /// Like all options, loopback is configured before the socket is bound,
+    /// so it applies to every frame written to the socket.
+    pub fn loopback(mut self, enabled: bool) -> CanSocketBuilder {
+        self.loopback = Some(enabled);
+        self
+    }
+
+    /// See `CanSocket::set_recv_own_msgs`.
+    pub fn recv_own_msgs(mut self, enabled: bool) -> CanSocketBuilder {
+        self.recv_own_msgs = Some(enabled);
+        self
     }
-}
\ No newline at end of file
+
+    /// See `CanSocket::set_nonblocking`.
+    pub fn nonblocking(mut self, nonblocking: bool) -> CanSocketBuilder {
+        self.nonblocking = Some(nonblocking);
+        self
+    }
+
+    /// See `CanSocket::set_filters`.
+    pub fn filters(mut self, filters: &[CanFilter]) -> CanSocketBuilder {
+        self.filters = Some(filters.to_vec());
+        self
+    }
+
+    /// See `CanSocket::set_error_mask`.
+    pub fn error_mask(mut self, mask: u32) -> CanSocketBuilder {
+        self.error_mask = Some(mask);
+        self
+    }
+
+    /// See `CanSocket::set_read_timeout`.
+    pub fn read_timeout(mut self, duration: time::Duration) -> CanSocketBuilder {
+        self.read_timeout = Some(duration);
+        self
+    }
+
+    /// Open a named CAN device, such as "vcan0", with the configured options.
+    pub fn open(&self, ifname: &str) -> Result<CanSocket, CanSocketOpenError> {
+        let if_index = if_nametoindex(ifname)?;
+        let socket = CanSocket { fd: CanSocket::open_socket()?, drop_log_level: Level::Debug, timestamp_resolution: TimestampResolution::Micros };
+
+        if let Some(enabled) = self.loopback {
+            socket.set_loopback(enabled)?;
+        }
+        if let Some(enabled) = self.recv_own_msgs {
+            socket.set_recv_own_msgs(enabled)?;
+        }
+        if let Some(nonblocking) = self.nonblocking {
+            socket.set_nonblocking(nonblocking)?;
+        }
+        if let Some(ref filters) = self.filters {
+            socket.set_filters(filters)?;
+        }
+        if let Some(mask) = self.error_mask {
+            socket.set_error_mask(mask)?;
+        }
+        if let Some(duration) = self.read_timeout {
+            socket.set_read_timeout(duration)?;
+        }
+
+        socket.bind(&CanAddr::new(if_index))?;
+        Ok(socket)
+    }
+}
+
+/// A `CanSocket` that can be shared between threads.
+///
+/// Clones refer to the same socket. Reads and writes are serialized
+/// internally, so a frame and its timestamp returned by `read` always belong
+/// together. Reading and writing can still happen concurrently.
+#[derive(Debug, Clone)]
+pub struct SharedCanSocket {
+    inner: Arc<SharedInner>,
+}
+
+#[derive(Debug)]
+struct SharedInner {
+    socket: CanSocket,
+    rx: Mutex<()>,
+    tx: Mutex<()>,
+}
+
+impl SharedCanSocket {
+    /// Wrap a socket for sharing between threads.
+    pub fn new(socket: CanSocket) -> SharedCanSocket {
+        SharedCanSocket {
+            inner: Arc::new(SharedInner {
+                socket,
+                rx: Mutex::new(()),
+                tx: Mutex::new(()),
+            }),
+        }
+    }
+
+    /// Blocking read a single can frame with timestamp, see `CanSocket::read`.
+    ///
+    /// Other threads reading from this socket wait until the frame has been
+    /// received.
+    pub fn read(&self) -> io::Result<(CanFrame, time::SystemTime)> {
+        let _guard = self.inner.rx.lock().unwrap_or_else(|e| e.into_inner());
+        self.inner.socket.read()
+    }
+
+    /// Write a single can frame, see `CanSocket::write`.
+    pub fn write(&self, frame: &CanFrame) -> io::Result<()> {
+        let _guard = self.inner.tx.lock().unwrap_or_else(|e| e.into_inner());
+        self.inner.socket.write(frame)
+    }
+
+    /// Access the underlying socket, e.g. for configuration.
+    ///
+    /// Calls made through the reference bypass the internal locking.
+    pub fn get_ref(&self) -> &CanSocket {
+        &self.inner.socket
+    }
+}
+
+/// A `CanSocket` that reopens its interface after it went away.
+///
+/// USB adapters disappear when unplugged and reappear under the same name.
+/// When a read or write fails because the interface is down or gone (see
+/// `IsInterfaceDown`), the interface is reopened with the options of the
+/// `CanSocketBuilder` it was created with, waiting with an exponential backoff
+/// between attempts. The failed operation is then retried once.
+#[derive(Debug)]
+pub struct ReconnectingCanSocket {
+    ifname: String,
+    builder: CanSocketBuilder,
+    socket: CanSocket,
+    min_backoff: time::Duration,
+    max_backoff: time::Duration,
+}
+
+impl ReconnectingCanSocket {
+    /// Open a named CAN device, configured by `builder` now and after every
+    /// reconnect.
+    pub fn open(ifname: &str, builder: CanSocketBuilder)
+                -> Result<ReconnectingCanSocket, CanSocketOpenError> {
+        let socket = builder.open(ifname)?;
+
+        Ok(ReconnectingCanSocket {
+            ifname: ifname.to_owned(),
+            builder,
+            socket,
+            min_backoff: time::Duration::from_millis(100),
+            max_backoff: time::Duration::from_secs(5),
+        })
+    }
+
+    /// Set the range of delays between reconnection attempts.
+    ///
+    /// Starts out waiting `min`, doubling on every failed attempt up to
+    /// `max`. Defaults to 100ms and 5s.
+    pub fn with_backoff(mut self, min: time::Duration, max: time::Duration) -> ReconnectingCanSocket {
+        self.min_backoff = min;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Blocking read a single can frame with timestamp, see `CanSocket::read`.
+    pub fn read(&mut self) -> io::Result<(CanFrame, time::SystemTime)> {
+        match self.socket.read() {
+            Err(ref e) if e.is_interface_down() => {
+                self.reconnect();
+                self.socket.read()
+            }
+            r => r,
+        }
+    }
+
+    /// Write a single can frame, see `CanSocket::write`.
+    pub fn write(&mut self, frame: &CanFrame) -> io::Result<()> {
+        match self.socket.write(frame) {
+            Err(ref e) if e.is_interface_down() => {
+                self.reconnect();
+                self.socket.write(frame)
+            }
+            r => r,
+        }
+    }
+
+    /// Reopen the interface, blocking until it succeeds.
+    pub fn reconnect(&mut self) {
+        let mut backoff = self.min_backoff;
+
+        loop {
+            match self.builder.open(&self.ifname) {
+                Ok(socket) => {
+                    debug!("Reconnected to {} (fd: {})", self.ifname, socket.fd);
+                    self.socket = socket;
+                    return;
+                }
+                Err(e) => debug!("Reconnecting to {} failed: {}", self.ifname, e),
+            }
+
+            thread::sleep(backoff);
+            backoff = cmp::min(backoff * 2, self.max_backoff);
+        }
+    }
+
+    /// Access the current underlying socket.
+    ///
+    /// Configuration changes made through the reference are lost on
+    /// reconnect, configure the `CanSocketBuilder` instead.
+    pub fn get_ref(&self) -> &CanSocket {
+        &self.socket
+    }
+}
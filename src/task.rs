@@ -1,3 +1,4 @@
+use std::io;
 
 /// Check an error return value for timeouts.
 ///
@@ -12,30 +13,20 @@ pub trait ShouldRetry {
     fn should_retry(&self) -> bool;
 }
 
-impl ShouldRetry<T> for std::io::Result<T> {
+impl ShouldRetry for io::Error {
     fn should_retry(&self) -> bool {
-        match *self {
-            Ok(_) => false,
-            Err(e) => match e.kind() {
-                // EAGAIN, EINPROGRESS and EWOULDBLOCK are the three possible codes
-                // returned when a timeout occurs. the stdlib already maps EAGAIN
-                // and EWOULDBLOCK os WouldBlock
-                std::io::ErrorKind::WouldBlock => true,
-                // however, EINPROGRESS is also valid
-                std::io::ErrorKind::Other => {
-                    if let Some(i) = self.raw_os_error() {
-                        i == libc::EINPROGRESS
-                    } else {
-                        false
-                    }
-                }
-                _ => false,
-            }
+        match self.kind() {
+            // EAGAIN, EINPROGRESS and EWOULDBLOCK are the three possible codes
+            // returned when a timeout occurs. the stdlib already maps EAGAIN
+            // and EWOULDBLOCK os WouldBlock
+            io::ErrorKind::WouldBlock => true,
+            // however, EINPROGRESS is also valid
+            _ => self.raw_os_error() == Some(libc::EINPROGRESS),
         }
     }
 }
 
-impl<E: std::fmt::Debug> ShouldRetry for std::io::Result<E> {
+impl<E> ShouldRetry for io::Result<E> {
     fn should_retry(&self) -> bool {
         if let Err(ref e) = *self {
             e.should_retry()
@@ -45,18 +36,31 @@ impl<E: std::fmt::Debug> ShouldRetry for std::io::Result<E> {
     }
 }
 
+/// Check an error return value for a vanished interface.
+///
+/// When the interface is taken down or a USB adapter is unplugged, reads and
+/// writes fail with `ENETDOWN` or `ENODEV`. Unlike timeouts (see
+/// `ShouldRetry`), retrying will not help; the socket has to be reopened once
+/// the interface is back.
+pub trait IsInterfaceDown {
+    /// Check for a missing interface
+    ///
+    /// If `true`, the interface is down or has been removed.
+    fn is_interface_down(&self) -> bool;
+}
+
+impl IsInterfaceDown for io::Error {
+    fn is_interface_down(&self) -> bool {
+        matches!(self.raw_os_error(), Some(libc::ENETDOWN) | Some(libc::ENODEV))
+    }
+}
 
-/// Blocking write a single can frame, retrying until it gets sent
-/// successfully.
-pub fn write_retry(&self, frame: &CanFrame) -> io::Result<()> {
-    loop {
-        match self.write_retry(frame) {
-            Ok(v) => return Ok(v),
-            Err(e) => {
-                if !e.should_retry() {
-                    return Err(e);
-                }
-            }
+impl<E> IsInterfaceDown for io::Result<E> {
+    fn is_interface_down(&self) -> bool {
+        if let Err(ref e) = *self {
+            e.is_interface_down()
+        } else {
+            false
         }
     }
-}
\ No newline at end of file
+}
@@ -1,34 +1,1009 @@
-use socket::CanSocket;
+use socket::{available_interfaces, CanAddr, CanSocket, SharedCanSocket};
+use cache::{FrameCache, MuxCollector};
+use task::{IsInterfaceDown, ShouldRetry};
+use transport::{CanTransport, MockCanSocket, UdpCanTransport};
+use errors::{CanError, CanSocketOpenError, ConstructionError, ControllerProblem, ControllerSpecificErrorInformation,
+             Location, ViolationType};
+use filter::{CanFilter, FilterSet};
+use interface::{CanInterface, CanLinkMonitor, LinkEvent, VcanGuard};
+use constants::{ARPHRD_CAN, IFLA_IFNAME};
+use frame::{AnyFrame, CanFdFrame, CanFrame, CanXlFrame, ErrorState, FrameBuilder, FrameFlags};
+use busload::{BusLoadEstimator, FloodDetector};
+use dlc::{fd_dlc_to_len, fd_len_to_dlc};
+use n2k::N2kFastPacket;
+use std::collections::BinaryHeap;
+use std::convert::TryFrom;
+use std::{io, mem};
 
 #[test]
 fn test_nonexistant_device() {
-    assert!(CanSocket::open("invalid").is_err());
+    match CanSocket::open("invalid") {
+        Err(CanSocketOpenError::InterfaceNotFound(ref name)) => assert_eq!(name, "invalid"),
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn test_frame_classification() {
+    let data = CanFrame::new(0x123, &[1, 2], false, false).unwrap();
+    assert!(data.is_standard());
+    assert!(data.is_data_frame());
+
+    let rtr = CanFrame::new(0x12345, &[], true, false).unwrap();
+    assert!(!rtr.is_standard());
+    assert!(!rtr.is_data_frame());
+
+    let err = CanFrame::new(0x1, &[], false, true).unwrap();
+    assert!(!err.is_data_frame());
+}
+
+#[test]
+fn test_frame_describe() {
+    let frame = CanFrame::new(0x123, &[0xDE, 0xAD], false, false).unwrap();
+    assert_eq!(frame.describe(), "SFF id=0x123 dlc=2 [DE AD]");
+
+    let frame = CanFrame::new(0x18DAF110, &[], true, false).unwrap();
+    assert_eq!(frame.describe(), "EFF RTR id=0x18DAF110 dlc=0 []");
+}
+
+#[test]
+fn test_frame_from_array() {
+    let frame = CanFrame::from_array(0x123, [1, 2, 3, 4, 5, 6, 7, 8], 3).unwrap();
+    assert_eq!(frame.id(), 0x123);
+    assert_eq!(frame.data(), &[1, 2, 3]);
+
+    assert!(CanFrame::from_array(0x123, [0; 8], 9).is_err());
+    assert!(CanFrame::from_array(0x20000000, [0; 8], 8).is_err());
+}
+
+#[test]
+fn test_xl_frame_construction() {
+    let frame = CanXlFrame::new(0x123, 0x01, 0xCAFE, &[1, 2, 3]).unwrap();
+    assert_eq!(frame.priority(), 0x123);
+    assert_eq!(frame.af(), 0xCAFE);
+    assert_eq!(frame.data(), &[1, 2, 3]);
+    assert!(frame.is_xl());
+
+    assert!(CanXlFrame::new(0x123, 0, 0, &[]).is_err());
+    assert!(CanXlFrame::new(0x123, 0, 0, &[0; 2049]).is_err());
+    assert!(CanXlFrame::new(0x800, 0, 0, &[0]).is_err());
+    assert_eq!(mem::size_of::<CanXlFrame>(), 2060);
+}
+
+#[test]
+fn test_frame_const_new() {
+    static HEARTBEAT: CanFrame = CanFrame::const_new(0x18FF0001, [1, 2, 0, 0, 0, 0, 0, 0], 2);
+
+    assert_eq!(HEARTBEAT.id(), 0x18FF0001);
+    assert!(HEARTBEAT.is_extended());
+    assert_eq!(HEARTBEAT.data(), &[1, 2]);
+}
+
+#[test]
+fn test_frame_priority() {
+    let low = CanFrame::new(0x200, &[], false, false).unwrap();
+    let high = CanFrame::new(0x100, &[], false, false).unwrap();
+    let rtr = CanFrame::new(0x100, &[], true, false).unwrap();
+    let ext = CanFrame::new(0x100 << 18, &[], false, false).unwrap();
+
+    assert!(high > low);
+    assert!(high > rtr);
+    assert!(rtr > ext);
+    assert!(ext > low);
+
+    let mut heap: BinaryHeap<CanFrame> = vec![low, ext, rtr, high].into_iter().collect();
+    assert_eq!(heap.pop(), Some(high));
+    assert_eq!(heap.pop(), Some(rtr));
+    assert_eq!(heap.pop(), Some(ext));
+    assert_eq!(heap.pop(), Some(low));
+}
+
+#[test]
+fn test_frame_explicit_format() {
+    let frame = CanFrame::new_extended(0x001, &[1]).unwrap();
+    assert!(frame.is_extended());
+    assert_eq!(frame.id(), 0x001);
+
+    let frame = CanFrame::new_standard(0x7FF, &[1]).unwrap();
+    assert!(frame.is_standard());
+
+    assert!(CanFrame::new_standard(0x800, &[]).is_err());
+    assert!(CanFrame::new_extended(0x20000000, &[]).is_err());
+}
+
+#[test]
+fn test_filter_matches() {
+    let data = CanFrame::new(0x123, &[], false, false).unwrap();
+    let rtr = CanFrame::new(0x123, &[], true, false).unwrap();
+    let other = CanFrame::new(0x124, &[], false, false).unwrap();
+    let err = CanFrame::new(0x040, &[], false, true).unwrap();
+
+    let filter = CanFilter::new(0x123, 0x7FF).unwrap();
+    assert!(filter.matches(&data));
+    assert!(filter.matches(&rtr));
+    assert!(!filter.matches(&other));
+    assert!(!filter.matches(&err));
+
+    let filter = CanFilter::new(0x123, 0x7FF).unwrap().data_only();
+    assert!(filter.matches(&data));
+    assert!(!filter.matches(&rtr));
+
+    let filter = CanFilter::new(0x123, 0x7FF).unwrap().rtr_only();
+    assert!(!filter.matches(&data));
+    assert!(filter.matches(&rtr));
+
+    let filter = CanFilter::new(0x123 | 0x20000000, 0x7FF).unwrap();
+    assert!(!filter.matches(&data));
+    assert!(filter.matches(&other));
+}
+
+#[test]
+fn test_frame_cache() {
+    let mut cache = FrameCache::new();
+    let frame = CanFrame::new(0x123, &[1, 2], false, false).unwrap();
+    let changed = CanFrame::new(0x123, &[1, 3], false, false).unwrap();
+
+    assert!(cache.update(frame));
+    assert!(!cache.update(frame));
+    assert!(cache.update(changed));
+    assert_eq!(cache.get(0x123), Some(&changed));
+    assert_eq!(cache.get(0x124), None);
+
+    // error frames and remote requests leave the data frame alone
+    let data = CanFrame::new(0x004, &[1], false, false).unwrap();
+    assert!(cache.update(data));
+    assert!(!cache.update(CanFrame::new(0x004, &[0; 8], false, true).unwrap()));
+    assert!(!cache.update(CanFrame::new(0x004, &[], true, false).unwrap()));
+    assert!(!cache.update(data));
+    assert_eq!(cache.get(0x004), Some(&data));
+
+    // standard and extended frames with the same id are kept apart
+    let extended = CanFrame::with_flags(0x123, &[1, 2], FrameFlags::EFF).unwrap();
+    assert!(cache.update(extended));
+    assert_eq!(cache.get(0x123), Some(&changed));
+    assert_eq!(cache.get(0x123 | 0x80000000), Some(&extended));
+    assert_eq!(cache.len(), 3);
+}
+
+#[test]
+fn test_fd_frame_construction() {
+    let frame = CanFdFrame::new(0x123, &[0xAB; 64], true, false).unwrap();
+    assert_eq!(frame.id(), 0x123);
+    assert_eq!(frame.data().len(), 64);
+    assert!(frame.is_brs());
+    assert!(!frame.is_esi());
+
+    assert!(CanFdFrame::new(0x123, &[0; 65], false, false).is_err());
+    assert_eq!(mem::size_of::<CanFdFrame>(), 72);
+}
+
+#[test]
+fn test_error_try_from_frame() {
+    let frame = CanFrame::new(0x20, &[], false, true).unwrap();
+    match CanError::try_from(&frame) {
+        Ok(CanError::NoAck) => (),
+        e => panic!("unexpected {:?}", e),
+    }
+    match CanError::try_from(frame) {
+        Ok(CanError::NoAck) => (),
+        e => panic!("unexpected {:?}", e),
+    }
+
+    let data = CanFrame::new(0x20, &[], false, false).unwrap();
+    assert!(CanError::try_from(data).is_err());
+}
+
+#[test]
+fn test_frame_get_signals() {
+    let frame = CanFrame::new(0x123, &[0x01, 0x02, 0x03, 0x04, 0x05], false, false).unwrap();
+    assert_eq!(frame.get_u8(4), Some(0x05));
+    assert_eq!(frame.get_u8(5), None);
+    assert_eq!(frame.get_u16_le(0), Some(0x0201));
+    assert_eq!(frame.get_u16_be(3), Some(0x0405));
+    assert_eq!(frame.get_u16_be(4), None);
+    assert_eq!(frame.get_u32_le(1), Some(0x05040302));
+    assert_eq!(frame.get_u32_be(0), Some(0x01020304));
+    assert_eq!(frame.get_u32_be(2), None);
+    assert_eq!(frame.get_u32_be(usize::MAX), None);
+}
+
+#[test]
+fn test_frame_builder() {
+    let frame = FrameBuilder::new()
+        .put_u8(0, 0xAA)
+        .put_u16_be(1, 0x0102)
+        .put_u32_le(4, 0x06050403)
+        .build(0x123)
+        .unwrap();
+    assert_eq!(frame.data(), &[0xAA, 0x01, 0x02, 0x00, 0x03, 0x04, 0x05, 0x06]);
+    assert_eq!(frame.get_u32_le(4), Some(0x06050403));
+
+    assert!(FrameBuilder::new().put_u16_le(7, 1).build(0x123).is_err());
+    assert_eq!(FrameBuilder::new().build(0x123).unwrap().data(), &[] as &[u8]);
+}
+
+#[test]
+fn test_socket_thread_safety() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<CanSocket>();
+    assert_send_sync::<SharedCanSocket>();
+}
+
+#[test]
+fn test_error_frame_class_not_extended() {
+    let err = CanFrame::new(0x800, &[], false, true).unwrap();
+    assert!(err.is_error());
+    assert!(!err.is_extended());
+    assert_eq!(err.err(), 0x800);
+}
+
+#[test]
+fn test_error_ctrl_err() {
+    let ctrl = CanFrame::new(0x04, &[0, 0x04, 0, 0, 0, 5, 6, 7], false, true).unwrap();
+    assert_eq!(ctrl.get_ctrl_err(), Some(&[5, 6, 7][..]));
+
+    let trx = CanFrame::new(0x10, &[0, 0, 0, 0, 0x04, 5, 6, 7], false, true).unwrap();
+    assert_eq!(trx.get_ctrl_err(), None);
+
+    let data = CanFrame::new(0x04, &[0, 0, 0, 0, 0, 5, 6, 7], false, false).unwrap();
+    assert_eq!(data.get_ctrl_err(), None);
+}
+
+#[test]
+fn test_frame_from_iter() {
+    let frame = CanFrame::from_iter(0x123, (1..4).map(|b| b * 2)).unwrap();
+    assert_eq!(frame, CanFrame::new(0x123, &[2, 4, 6], false, false).unwrap());
+
+    let frame = CanFrame::from_iter(0x12345, vec![0xFF; 8]).unwrap();
+    assert!(frame.is_extended());
+    assert_eq!(frame.data().len(), 8);
+
+    assert!(CanFrame::from_iter(0x123, 0..9).is_err());
+}
+
+#[test]
+fn test_filter_optimize() {
+    let wide = CanFilter::new(0x100, 0x700).unwrap();
+    let narrow = CanFilter::new(0x123, 0x7FF).unwrap();
+    let other = CanFilter::new(0x200, 0x7FF).unwrap();
+    let inverted = CanFilter::new(0x123 | 0x20000000, 0x7FF).unwrap();
+
+    let optimized = CanFilter::optimize(&[narrow, wide, other, wide, inverted]);
+    assert_eq!(optimized.len(), 3);
+    assert!(optimized[0].matches(&CanFrame::new(0x1FF, &[], false, false).unwrap()));
+    assert!(optimized[1].matches(&CanFrame::new(0x200, &[], false, false).unwrap()));
+
+    // everything is covered by the default filter
+    let optimized = CanFilter::optimize(&[narrow, CanFilter::default(), other]);
+    assert_eq!(optimized.len(), 1);
+}
+
+#[test]
+fn test_error_is_interface_down() {
+    let down = io::Error::from_raw_os_error(libc::ENETDOWN);
+    assert!(down.is_interface_down());
+    assert!(!down.should_retry());
+
+    let again = io::Error::from_raw_os_error(libc::EAGAIN);
+    assert!(!again.is_interface_down());
+    assert!(again.should_retry());
+
+    let r: io::Result<()> = Err(io::Error::from_raw_os_error(libc::ENODEV));
+    assert!(r.is_interface_down());
+}
+
+#[test]
+fn test_mock_socket() {
+    fn echo<T: CanTransport>(transport: &T) -> io::Result<()> {
+        let frame = transport.recv()?;
+        transport.send(&frame)
+    }
+
+    let mock = MockCanSocket::new();
+    let frame = CanFrame::new(0x123, &[1, 2], false, false).unwrap();
+    mock.push_rx(frame);
+
+    echo(&mock).unwrap();
+    assert_eq!(mock.take_tx(), vec![frame]);
+    assert!(mock.take_tx().is_empty());
+    assert!(echo(&mock).should_retry());
+
+    let err = CanFrame::new(0x20, &[], false, true).unwrap();
+    assert!(mock.send(&err).is_err());
+}
+
+#[test]
+fn test_dyn_transport() {
+    fn forward(from: &dyn CanTransport, to: &dyn CanTransport) -> io::Result<()> {
+        to.send(&from.recv()?)
+    }
+
+    let rx = MockCanSocket::new();
+    let tx: Box<dyn CanTransport> = Box::new(MockCanSocket::new());
+    let frame = CanFrame::new(0x123, &[1], false, false).unwrap();
+    rx.push_rx(frame);
+
+    forward(&rx, &tx).unwrap();
+    assert!(forward(&rx, &tx).should_retry());
+
+    let shared = ::std::sync::Arc::new(MockCanSocket::new());
+    rx.push_rx(frame);
+    forward(&rx, &shared).unwrap();
+    assert_eq!(shared.take_tx(), vec![frame]);
+}
+
+#[test]
+fn test_filter_from_range() {
+    let filters = CanFilter::from_range(0x700, 0x7FF).unwrap();
+    assert_eq!(filters.len(), 1);
+
+    let filters = CanFilter::from_range(0x101, 0x10E).unwrap();
+    assert_eq!(filters.len(), 6);
+
+    let matches = |filters: &[CanFilter], id: u32| {
+        let frame = CanFrame::new(id, &[], false, false).unwrap();
+        filters.iter().any(|f| f.matches(&frame))
+    };
+    for id in 0x0F0..0x120 {
+        assert_eq!(matches(&filters, id), (0x101..=0x10E).contains(&id));
+    }
+    assert!(!matches(&filters, 0x18000105));
+
+    let filters = CanFilter::from_range(0x1000, 0x1FFFFFFF).unwrap();
+    assert!(matches(&filters, 0x1000));
+    assert!(matches(&filters, 0x1FFFFFFF));
+    assert!(!matches(&filters, 0xFFF));
+
+    assert!(CanFilter::from_range(0, 0x20000000).is_err());
+    assert!(CanFilter::from_range(0x10, 0x0F).unwrap().is_empty());
+}
+
+#[test]
+fn test_frame_checksums() {
+    let frame = CanFrame::new(0x123, &[0xF2, 0x01, 0x83, 0x37], false, false).unwrap();
+    assert_eq!(frame.checksum_xor(0..3), 0xF2 ^ 0x01 ^ 0x83);
+    assert_eq!(frame.checksum_xor(0..0), 0);
+    assert_eq!(frame.checksum_crc8(0..3), frame.data()[3]);
+
+    let zero = CanFrame::new(0x123, &[0, 0, 0, 0], false, false).unwrap();
+    assert_eq!(zero.checksum_crc8(0..4), 0x59);
+}
+
+#[test]
+fn test_available_interfaces() {
+    let names = available_interfaces().unwrap();
+    assert!(!names.iter().any(|name| name == "lo"));
+}
+
+#[test]
+fn test_frame_respond_to_rtr() {
+    let request = CanFrame::new(0x123, &[], true, false).unwrap();
+    let response = CanFrame::respond_to_rtr(&request, &[1, 2]).unwrap();
+    assert!(!response.is_rtr());
+    assert!(response.is_standard());
+    assert_eq!(response, CanFrame::new(0x123, &[1, 2], false, false).unwrap());
+
+    let request = CanFrame::new(0x18DAF110, &[], true, false).unwrap();
+    let response = CanFrame::respond_to_rtr(&request, &[0; 8]).unwrap();
+    assert!(response.is_extended());
+    assert_eq!(response.id(), 0x18DAF110);
+    assert!(CanFrame::respond_to_rtr(&request, &[0; 9]).is_err());
+}
+
+#[test]
+fn test_interface_lookup() {
+    assert!(CanInterface::open("invalid").is_err());
+
+    let lo = CanInterface::try_from("lo").unwrap();
+    assert_eq!(lo.name(), "lo");
+    assert!(lo.index() > 0);
+    assert!(lo.tx_queue_len().is_ok());
+}
+
+#[test]
+fn test_error_all_from_frame() {
+    let frame = CanFrame::new(0x84, &[0, 0x10, 0, 0, 0, 0, 0, 0], false, true).unwrap();
+    assert!(CanError::from_frame(&frame).is_err());
+
+    let errors = CanError::all_from_frame(&frame).unwrap();
+    assert_eq!(errors.len(), 2);
+    match (errors[0], errors[1]) {
+        (CanError::ControllerProblem(ControllerProblem::ReceiveErrorPassive), CanError::BusError) => (),
+        e => panic!("unexpected {:?}", e),
+    }
+
+    let data = CanFrame::new(0x84, &[], false, false).unwrap();
+    assert!(CanError::all_from_frame(&data).is_err());
+
+    // error counters (CAN_ERR_CNT) reported alongside a controller problem
+    let frame = CanFrame::new(0x204, &[0, 0x10, 0, 0, 0, 0, 0x80, 0], false, true).unwrap();
+    assert_eq!(CanError::all_from_frame(&frame).unwrap(),
+               vec![CanError::ControllerProblem(ControllerProblem::ReceiveErrorPassive),
+                    CanError::Unknown(0x200)]);
+}
+
+#[test]
+fn test_fd_frame_conversion() {
+    let frame = CanFrame::new(0x12345, &[1, 2, 3], false, false).unwrap();
+    let fd = CanFdFrame::from(frame);
+    assert_eq!(fd.id(), 0x12345);
+    assert!(fd.is_extended());
+    assert!(!fd.is_brs());
+    assert_eq!(fd.data(), &[1, 2, 3]);
+    assert_eq!(CanFrame::try_from(fd).unwrap(), frame);
+
+    let long = CanFdFrame::new(0x123, &[0; 12], true, false).unwrap();
+    assert!(CanFrame::try_from(long).is_err());
+}
+
+#[test]
+fn test_construction_error_values() {
+    let e = CanFrame::new(0x20000000, &[], false, false).unwrap_err();
+    assert_eq!(e.to_string(), "CAN ID 0x20000000 too large");
+
+    let e = CanFrame::new(0x123, &[0; 9], false, false).unwrap_err();
+    assert_eq!(e.to_string(), "Payload of 9 bytes is larger than the CAN frame maximum");
+
+    match CanFrame::from_iter(0x123, 0..20) {
+        Err(ConstructionError::TooMuchData(20)) => (),
+        r => panic!("unexpected {:?}", r),
+    }
+    match FrameBuilder::new().put_u32_le(6, 1).build(0x123) {
+        Err(ConstructionError::TooMuchData(10)) => (),
+        r => panic!("unexpected {:?}", r),
+    }
+}
+
+#[test]
+fn test_frame_bytes_roundtrip() {
+    let frame = CanFrame::new(0x12345, &[1, 2, 3], false, false).unwrap();
+    let bytes = frame.as_bytes();
+    assert_eq!(bytes.len(), 16);
+    assert_eq!(bytes[4], 3);
+    assert_eq!(CanFrame::try_from(bytes).unwrap(), frame);
+
+    assert!(CanFrame::try_from(&bytes[..15]).is_err());
+    let mut bad = [0u8; 16];
+    bad[4] = 9;
+    assert!(CanFrame::try_from(&bad[..]).is_err());
+}
+
+#[test]
+fn test_udp_transport() {
+    let a = UdpCanTransport::connect("127.0.0.1:0", "127.0.0.1:9").unwrap();
+    let b = UdpCanTransport::connect("127.0.0.1:0", a.get_ref().local_addr().unwrap()).unwrap();
+    a.get_ref().connect(b.get_ref().local_addr().unwrap()).unwrap();
+
+    let frame = CanFrame::new(0x123, &[1, 2], false, false).unwrap();
+    b.send(&frame).unwrap();
+    assert_eq!(a.recv().unwrap(), frame);
+}
+
+#[test]
+fn test_fd_error_state_hint() {
+    let active = CanFdFrame::new(0x123, &[1], true, false).unwrap();
+    assert_eq!(active.error_state_hint(), ErrorState::ErrorActive);
+
+    let passive = CanFdFrame::new(0x123, &[1], true, true).unwrap();
+    assert!(passive.is_esi());
+    assert_eq!(passive.error_state_hint(), ErrorState::ErrorPassive);
+}
+
+#[test]
+fn test_socket_into_fd_and_close() {
+    use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
+
+    let raw = ::std::fs::File::open("/dev/null").unwrap().into_raw_fd();
+    let sock = unsafe { CanSocket::from_raw_fd(raw) };
+    let owned = sock.into_fd();
+    assert_eq!(owned.as_raw_fd(), raw);
+
+    let mut sock = unsafe { CanSocket::from_raw_fd(owned.into_raw_fd()) };
+    sock.close().unwrap();
+    sock.close().unwrap();
+}
+
+#[test]
+fn test_bus_load_estimator() {
+    use std::time::{Duration, Instant};
+
+    let mut est = BusLoadEstimator::new(500_000, Duration::from_secs(1));
+    let frame: AnyFrame = CanFrame::new(0x123, &[0; 8], false, false).unwrap().into();
+    // 47 bits overhead, 64 payload and 24 stuff bits at 2 us each
+    assert_eq!(est.frame_duration(&frame), Duration::from_micros(270));
+
+    let start = Instant::now();
+    for i in 0..100 {
+        est.push_at(&frame, start + Duration::from_millis(i));
+    }
+    let load = est.load_at(start + Duration::from_millis(100));
+    assert!((load - 2.7).abs() < 0.01);
+
+    // all frames have left the window
+    assert_eq!(est.load_at(start + Duration::from_secs(2)), 0.0);
+
+    let fd: AnyFrame = CanFdFrame::new(0x123, &[0; 64], true, false).unwrap().into();
+    let fast = BusLoadEstimator::new(500_000, Duration::from_secs(1)).with_data_bitrate(2_000_000);
+    assert!(fast.frame_duration(&fd) < est.frame_duration(&fd));
+}
+
+#[test]
+fn test_permission_denied_display() {
+    let e = CanSocketOpenError::PermissionDenied(io::Error::from_raw_os_error(libc::EPERM));
+    assert!(e.to_string().contains("CAP_NET_RAW"));
+}
+
+#[test]
+fn test_mux_collector() {
+    use std::time::{Duration, Instant};
+
+    let mut mux = MuxCollector::new(0x200, 0).with_timeout(Duration::from_secs(1));
+    let start = Instant::now();
+
+    assert!(mux.feed_at(CanFrame::new(0x200, &[0, 10], false, false).unwrap(), start));
+    assert!(mux.feed_at(CanFrame::new(0x200, &[1, 11], false, false).unwrap(), start));
+    assert!(mux.feed_at(CanFrame::new(0x200, &[0, 12], false, false).unwrap(), start));
+    assert!(!mux.feed_at(CanFrame::new(0x201, &[2, 13], false, false).unwrap(), start));
+    assert!(!mux.feed_at(CanFrame::new(0x200, &[], false, false).unwrap(), start));
+
+    assert_eq!(mux.snapshot().len(), 2);
+    assert_eq!(mux.snapshot()[&0].data(), &[0, 12]);
+    assert!(mux.is_complete(&[0, 1]));
+    assert!(!mux.is_complete(&[0, 1, 2]));
+
+    let later = start + Duration::from_secs(2);
+    assert!(mux.feed_at(CanFrame::new(0x200, &[2, 14], false, false).unwrap(), later));
+    assert_eq!(mux.snapshot().len(), 1);
+    assert!(mux.snapshot().contains_key(&2));
+}
+
+#[test]
+fn test_filter_new_extended() {
+    let filter = CanFilter::new_extended(0x5, 0x1FFFFFFF).unwrap();
+
+    let extended = CanFrame::new_extended(0x5, &[]).unwrap();
+    let standard = CanFrame::new(0x5, &[], false, false).unwrap();
+    assert!(filter.matches(&extended));
+    assert!(!filter.matches(&standard));
+
+    assert!(CanFilter::new_extended(0x2000_0000, 0x1FFFFFFF).is_err());
+}
+
+#[test]
+fn test_link_monitor_open() {
+    assert!(CanLinkMonitor::open().is_ok());
+}
+
+// struct nlmsghdr, struct ifinfomsg and an IFLA_IFNAME attribute
+fn link_message(ty: u16, if_index: u32, flags: u32, name: &str) -> Vec<u8> {
+    let attr_len = 4 + name.len() + 1;
+    let len = 16 + 16 + ((attr_len + 3) & !3);
+
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(len as u32).to_ne_bytes());
+    buf.extend_from_slice(&ty.to_ne_bytes());
+    buf.extend_from_slice(&[0; 10]);
+
+    buf.extend_from_slice(&[libc::AF_UNSPEC as u8, 0]);
+    buf.extend_from_slice(&ARPHRD_CAN.to_ne_bytes());
+    buf.extend_from_slice(&if_index.to_ne_bytes());
+    buf.extend_from_slice(&flags.to_ne_bytes());
+    buf.extend_from_slice(&[0; 4]);
+
+    buf.extend_from_slice(&(attr_len as u16).to_ne_bytes());
+    buf.extend_from_slice(&IFLA_IFNAME.to_ne_bytes());
+    buf.extend_from_slice(name.as_bytes());
+    buf.resize(len, 0);
+    buf
+}
+
+#[test]
+fn test_link_events() {
+    let mut monitor = CanLinkMonitor::open().unwrap();
+    assert!(monitor.pending.is_empty());
+
+    // an index no interface of the machine running the test has
+    let if_index = 0x7FFF_0005;
+    let up = (libc::IFF_UP | libc::IFF_RUNNING) as u32;
+
+    let mut buf = link_message(libc::RTM_NEWLINK, if_index, up, "can0");
+    buf.extend(link_message(libc::RTM_NEWLINK, if_index, up, "can0"));
+    buf.extend(link_message(libc::RTM_NEWLINK, if_index, libc::IFF_UP as u32, "can0"));
+    buf.extend(link_message(libc::RTM_DELLINK, if_index, 0, "can0"));
+    monitor.parse(&buf);
+
+    let can0 = |event: Option<LinkEvent>| match event {
+        Some(LinkEvent::Up(i)) | Some(LinkEvent::Down(i)) | Some(LinkEvent::Removed(i)) => {
+            assert_eq!((i.name(), i.index()), ("can0", if_index));
+        }
+        None => panic!("missing event"),
+    };
+
+    assert!(matches!(monitor.pending.front(), Some(LinkEvent::Up(_))));
+    can0(monitor.pending.pop_front());
+    assert!(matches!(monitor.pending.front(), Some(LinkEvent::Down(_))));
+    can0(monitor.pending.pop_front());
+    assert!(matches!(monitor.pending.front(), Some(LinkEvent::Removed(_))));
+    can0(monitor.pending.pop_front());
+    assert_eq!(monitor.pending.pop_front(), None);
+}
+
+#[test]
+fn test_frame_flags() {
+    let frame = CanFrame::with_flags(0x5, &[1], FrameFlags::EFF | FrameFlags::RTR).unwrap();
+    assert!(frame.is_extended());
+    assert!(frame.is_rtr());
+    assert_eq!(frame.id(), 0x5);
+    assert_eq!(frame.flags(), FrameFlags::EFF | FrameFlags::RTR);
+    assert!(!frame.flags().contains(FrameFlags::ERR));
+
+    let frame = CanFrame::with_flags(0x12345, &[], FrameFlags::empty()).unwrap();
+    assert_eq!(frame.flags(), FrameFlags::EFF);
+
+    let frame = CanFrame::new(0x123, &[], false, false).unwrap();
+    assert!(frame.flags().is_empty());
+}
+
+#[test]
+fn test_rtr_equality() {
+    let rtr4 = CanFrame::with_flags(0x123, &[0; 4], FrameFlags::RTR).unwrap();
+    let rtr8 = CanFrame::with_flags(0x123, &[0; 8], FrameFlags::RTR).unwrap();
+    assert_ne!(rtr4, rtr8);
+    assert_ne!(rtr4.cmp(&rtr8), ::std::cmp::Ordering::Equal);
+
+    // the payload of a remote frame is not compared
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(rtr4.as_bytes());
+    bytes[8] = 0xFF;
+    let garbage = CanFrame::try_from(&bytes[..]).unwrap();
+    assert_eq!(rtr4, garbage);
+    assert_eq!(rtr4.cmp(&garbage), ::std::cmp::Ordering::Equal);
+}
+
+#[test]
+fn test_fd_dlc_conversion() {
+    for dlc in 0..16 {
+        assert_eq!(fd_len_to_dlc(fd_dlc_to_len(dlc)), Some(dlc));
+    }
+    assert_eq!(fd_dlc_to_len(9), 12);
+    assert_eq!(fd_dlc_to_len(15), 64);
+    assert_eq!(fd_len_to_dlc(10), Some(9));
+    assert_eq!(fd_len_to_dlc(33), Some(14));
+    assert_eq!(fd_len_to_dlc(65), None);
+
+    // payloads between valid lengths are padded
+    let frame = CanFdFrame::new(0x123, &[1; 10], false, false).unwrap();
+    assert_eq!(frame.data(), &[1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0]);
+}
+
+#[test]
+fn test_interface_one_shot_unsupported() {
+    // the loopback device has no CAN controller mode
+    let lo = CanInterface::open("lo").unwrap();
+    assert_eq!(lo.is_one_shot().unwrap_err().kind(), io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn test_n2k_fast_packet() {
+    // PGN 129029 (GNSS position data) from node 0x23 at priority 3
+    let id = (3 << 26) | (129029 << 8) | 0x23;
+    let payload: Vec<u8> = (0..43).collect();
+
+    let frames = N2kFastPacket::segment(id, 2, &payload).unwrap();
+    assert_eq!(frames.len(), 7);
+    assert_eq!(frames[0].data()[..2], [0x40, 43]);
+    assert_eq!(frames[6].data(), &[0x46, 41, 42, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+    let mut assembler = N2kFastPacket::new();
+    for frame in &frames[..6] {
+        assert_eq!(assembler.feed(frame), None);
+    }
+    let msg = assembler.feed(&frames[6]).unwrap();
+    assert_eq!(msg.pgn, 129029);
+    assert_eq!(msg.source, 0x23);
+    assert_eq!(msg.priority, 3);
+    assert_eq!(msg.data, payload);
+    assert_eq!(assembler.pending(), 0);
+
+    // a missing frame drops the message
+    assert_eq!(assembler.feed(&frames[0]), None);
+    assert_eq!(assembler.feed(&frames[2]), None);
+    assert_eq!(assembler.pending(), 0);
+
+    assert!(N2kFastPacket::segment(id, 0, &[0; 224]).is_err());
+}
+
+#[test]
+fn test_vcan_guard() {
+    let vcan = match VcanGuard::create("vcantest0") {
+        Ok(vcan) => vcan,
+        Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied ||
+                      e.kind() == io::ErrorKind::Unsupported => return,
+        Err(e) => panic!("creating vcan interface failed: {}", e),
+    };
+
+    let cs = CanSocket::open(vcan.name()).unwrap();
+    cs.set_recv_own_msgs(true).unwrap();
+    let frame = CanFrame::new(0x123, &[1, 2, 3], false, false).unwrap();
+    cs.write(&frame).unwrap();
+    assert_eq!(cs.read().unwrap().0, frame);
+
+    drop(cs);
+    drop(vcan);
+    assert!(CanInterface::open("vcantest0").is_err());
+}
+
+#[test]
+fn test_error_to_frame_round_trip() {
+    let errors = [
+        CanError::TransmitTimeout,
+        CanError::LostArbitration(12),
+        CanError::ControllerProblem(ControllerProblem::TransmitErrorPassive),
+        CanError::ProtocolViolation {
+            vtype: ViolationType::BitStuffingError,
+            location: Location::CrcDelimiter,
+        },
+        CanError::TransceiverError,
+        CanError::NoAck,
+        CanError::BusOff,
+        CanError::BusError,
+        CanError::Restarted,
+    ];
+
+    for err in errors.iter() {
+        let frame = err.to_frame().unwrap();
+        assert!(frame.is_error());
+        assert_eq!(CanError::from_frame(&frame).unwrap(), *err);
+    }
+
+    let frame = CanError::ProtocolViolation {
+        vtype: ViolationType::BitStuffingError,
+        location: Location::CrcDelimiter,
+    }.to_frame().unwrap();
+    assert_eq!(frame.err(), 0x08);
+    assert_eq!(frame.data(), &[0, 0, 0x04, 0x18, 0, 0, 0, 0]);
+
+    // unknown classes are encoded as is, but cannot be decoded
+    let frame = CanError::Unknown(0x200).to_frame().unwrap();
+    assert_eq!(frame.err(), 0x200);
+    assert!(CanError::from_frame(&frame).is_err());
+    assert!(CanError::Unknown(0xFFFFFFFF).to_frame().is_err());
+}
+
+#[test]
+fn test_can_addr() {
+    let addr = CanAddr::new(3);
+    assert_eq!(addr.if_index(), 3);
+    assert_eq!((addr.rx_id(), addr.tx_id()), (0, 0));
+
+    let addr = addr.with_tp_ids(0x7E8, 0x7E0);
+    assert_eq!(addr.if_index(), 3);
+    assert_eq!((addr.rx_id(), addr.tx_id()), (0x7E8, 0x7E0));
+    assert_eq!(mem::size_of::<CanAddr>(), 16);
+}
+
+#[test]
+fn test_filter_set() {
+    let matches = |filters: &[CanFilter], frame: &CanFrame| filters.iter().any(|f| f.matches(frame));
+    let std = |id| CanFrame::new(id, &[], false, false).unwrap();
+    let ext = |id| CanFrame::new_extended(id, &[]).unwrap();
+
+    let filters = FilterSet::new()
+        .accept(0x100..=0x1FF)
+        .accept_exact(0x7E8)
+        .reject(0x180)
+        .reject(0x000)
+        .build()
+        .unwrap();
+    for id in 0..=0x7FF {
+        let expected = (0x100..=0x1FF).contains(&id) && id != 0x180 || id == 0x7E8;
+        assert_eq!(matches(&filters, &std(id)), expected);
+    }
+    assert!(!matches(&filters, &ext(0x100)));
+
+    // everything but the rejected ids
+    assert!(matches(&FilterSet::new().build().unwrap(), &ext(0x1234)));
+
+    let filters = FilterSet::new().reject(0x000).build().unwrap();
+    assert_eq!(filters.len(), 1);
+    assert!(!matches(&filters, &std(0x000)));
+    assert!(!matches(&filters, &ext(0x000)));
+    assert!(matches(&filters, &std(0x001)));
+    assert!(matches(&filters, &ext(0x800)));
+
+    let filters = FilterSet::new().reject(0x000).reject(0x1234).build().unwrap();
+    assert!(!matches(&filters, &std(0x000)));
+    assert!(!matches(&filters, &ext(0x1234)));
+    assert!(matches(&filters, &std(0x7FF)));
+    assert!(matches(&filters, &ext(0x000001)));
+    assert!(matches(&filters, &ext(0x1FFFFFFF)));
+
+    // pieces of an extended range stay extended
+    let filters = FilterSet::new().accept(0x0..=0x1000).reject(0x800).build().unwrap();
+    assert!(matches(&filters, &ext(0x7FF)));
+    assert!(!matches(&filters, &std(0x7FF)));
+    assert!(!matches(&filters, &ext(0x800)));
+
+    assert!(FilterSet::new().accept(0..=0x20000000).build().is_err());
+    assert!(FilterSet::new().reject(0x20000000).build().is_err());
+
+    // isolated ids cannot be merged into fewer filters
+    let set = (0..600).fold(FilterSet::new(), |set, i| set.accept_exact(i * 2));
+    match set.build() {
+        Err(ConstructionError::TooManyFilters(n)) => assert_eq!(n, 600),
+        r => panic!("unexpected {:?}", r),
+    }
+}
+
+#[test]
+fn test_frame_from_bytes_ref() {
+    let frames = [
+        CanFrame::new(0x123, &[1, 2, 3], false, false).unwrap(),
+        CanFrame::new_extended(0x12345, &[4; 8]).unwrap(),
+    ];
+
+    // a buffer as filled by recvmmsg, aligned like the frames themselves
+    let mut buf = [0u32; 8];
+    for (slot, frame) in buf.chunks_mut(4).zip(frames.iter()) {
+        let bytes = frame.as_bytes();
+        for (word, chunk) in slot.iter_mut().zip(bytes.chunks(4)) {
+            *word = u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const u8, 32) };
+
+    for (slot, frame) in bytes.chunks(16).zip(frames.iter()) {
+        assert_eq!(CanFrame::from_bytes_ref(slot).unwrap(), frame);
+    }
+
+    match CanFrame::from_bytes_ref(&bytes[1..17]) {
+        Err(ConstructionError::Misaligned(_)) => (),
+        r => panic!("unexpected {:?}", r),
+    }
+    assert!(CanFrame::from_bytes_ref(&bytes[..15]).is_err());
+    assert!(CanFrame::from_bytes_ref(bytes).is_err());
+}
+
+#[test]
+fn test_flood_detector() {
+    use std::time::{Duration, Instant};
+
+    let mut detector = FloodDetector::new(100.0, Duration::from_secs(1));
+    let flood = CanFrame::new(0x001, &[0xFF], false, false).unwrap();
+    let normal = CanFrame::new(0x100, &[1], false, false).unwrap();
+    let start = Instant::now();
+
+    // 100 frames/s are within the threshold
+    for i in 0..100 {
+        let at = start + Duration::from_millis(10 * i);
+        assert!(detector.feed_at(&flood, at).is_none());
+        assert!(detector.feed_at(&normal, at).is_none());
+    }
+
+    // doubling the rate of 0x001 raises a single alert
+    let mut alerts = Vec::new();
+    for i in 0..200 {
+        let at = start + Duration::from_secs(1) + Duration::from_millis(5 * i);
+        alerts.extend(detector.feed_at(&flood, at));
+    }
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].id, 0x001);
+    assert!(!alerts[0].extended);
+    assert!(alerts[0].rate > 100.0);
+
+    // after a pause the id is below the threshold again
+    let at = start + Duration::from_secs(5);
+    assert!(detector.feed_at(&flood, at).is_none());
+
+    // ids that went quiet are forgotten, 0x100 has not been seen since
+    assert_eq!(detector.tracked_ids(), 1);
+    for i in 0..1000 {
+        let frame = CanFrame::new(0x10000 + i, &[], false, false).unwrap();
+        detector.feed_at(&frame, at + Duration::from_millis(10 * i as u64));
+    }
+    // swept once per window, so at most two windows worth of ids are kept
+    assert!(detector.tracked_ids() <= 201);
+}
+
+#[test]
+fn test_frame_validate() {
+    assert!(CanFrame::new(0x123, &[1, 2], false, false).unwrap().validate().is_ok());
+    assert!(CanFrame::new(0x12345, &[1, 2], true, false).unwrap().validate().is_ok());
+    assert!(CanFrame::new(0x1FFFF, &[0; 8], false, true).unwrap().validate().is_ok());
+
+    let frame = |id: u32, len: u8| {
+        let mut bytes = [0u8; 16];
+        bytes[..4].copy_from_slice(&id.to_ne_bytes());
+        bytes[4] = len;
+        let frame: CanFrame = unsafe { mem::transmute(bytes) };
+        frame
+    };
+
+    match frame(0x123, 9).validate() {
+        Err(ConstructionError::TooMuchData(9)) => (),
+        r => panic!("unexpected {:?}", r),
+    }
+    match frame(0x800, 0).validate() {
+        Err(ConstructionError::IDTooLarge(0x800)) => (),
+        r => panic!("unexpected {:?}", r),
+    }
+    match frame(0x20000004 | 0x40000000, 8).validate() {
+        Err(ConstructionError::InvalidFlags(_)) => (),
+        r => panic!("unexpected {:?}", r),
+    }
+    assert!(frame(0x20000004 | 0x80000000, 8).validate().is_err());
+    assert!(frame(0x80000800, 8).validate().is_ok());
+
+    // a corrupt data length must not make data() panic
+    assert_eq!(frame(0x123, 0xFF).data().len(), 8);
 }
 
 
 #[cfg(feature = "vcan_tests")]
 mod vcan_tests {
-    use {CanFrame, CanInterface, CanSocket, ERR_MASK_ALL, ERR_MASK_NONE};
-    use std::time;
+    use frame::CanFrame;
+    use interface::{CanInterface, VcanGuard};
+    use socket::{available_interfaces, replay, CanBus, CanEvent, CanSocket, CanSocketBuilder, ReconnectingCanSocket,
+                 TimestampResolution, ERR_MASK_ALL, ERR_MASK_NONE};
+    use std::convert::TryFrom;
+    use std::io;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::{thread, time};
+    use task::ShouldRetry;
+    use filter::CanFilter;
+    use j1939::J1939Socket;
+    use worker::{RxWorker, TxWorker};
+    use bridge::Bridge;
+
+    /// Create a vcan interface with a unique name, removed at the end of the
+    /// test. Skips the test if creating interfaces is not permitted or the
+    /// vcan module is not available.
+    macro_rules! vcan {
+        () => {
+            match create_vcan() {
+                Ok(vcan) => vcan,
+                Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied ||
+                              e.kind() == io::ErrorKind::Unsupported => {
+                    return println!("skipping test: {}", e);
+                }
+                Err(e) => panic!("creating vcan interface failed: {}", e),
+            }
+        };
+    }
+
+    fn create_vcan() -> io::Result<VcanGuard> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        // tests run in parallel, also across test binaries
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        VcanGuard::create(&format!("vct{}_{}", process::id() % 100_000, n))
+    }
 
     #[test]
-    fn vcan0_timeout() {
-        let s = CanSocket::open("vcan0").unwrap();
+    fn vcan_timeout() {
+        let vcan = vcan!();
+        let s = CanSocket::open(vcan.name()).unwrap();
         s.set_read_timeout(time::Duration::from_millis(100))
             .unwrap();
         assert!(s.read().should_retry());
     }
 
     #[test]
-    fn vcan0_set_error_mask() {
-        let cs = CanSocket::open("vcan0").unwrap();
+    fn vcan_set_error_mask() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
         cs.set_error_mask(ERR_MASK_ALL).unwrap();
+        assert_eq!(cs.error_mask().unwrap(), ERR_MASK_ALL);
+        assert!(cs.error_reporting_enabled().unwrap());
         cs.set_error_mask(ERR_MASK_NONE).unwrap();
+        assert!(!cs.error_reporting_enabled().unwrap());
     }
 
     #[test]
-    fn vcan0_enable_own_loopback() {
-        let cs = CanSocket::open("vcan0").unwrap();
+    fn vcan_enable_own_loopback() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
         cs.set_loopback(true).unwrap();
         cs.set_recv_own_msgs(true).unwrap();
 
@@ -39,18 +1014,566 @@ mod vcan_tests {
     }
 
     #[test]
-    fn vcan0_set_down() {
-        let can_if = CanInterface::open("vcan0").unwrap();
+    fn vcan_set_down() {
+        let vcan = vcan!();
+        let can_if = CanInterface::open(vcan.name()).unwrap();
         can_if.bring_down().unwrap();
     }
 
     #[test]
-    fn vcan0_test_nonblocking() {
-        let cs = CanSocket::open("vcan0").unwrap();
-        cs.set_nonblocking(true);
+    fn vcan_test_nonblocking() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_nonblocking(true).unwrap();
 
         // no timeout set, but should return immediately
         assert!(cs.read().should_retry());
     }
 
+    #[test]
+    fn vcan_read_own_flags() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_loopback(true).unwrap();
+        cs.set_recv_own_msgs(true).unwrap();
+
+        let frame = CanFrame::new(0x123, &[], false, false).unwrap();
+
+        cs.write(&frame).unwrap();
+        let (_, flags) = cs.read_with_flags().unwrap();
+        assert!(flags.is_own);
+        assert!(flags.is_local);
+    }
+
+    #[test]
+    fn vcan_drain() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_recv_own_msgs(true).unwrap();
+
+        let frame = CanFrame::new(0x123, &[], false, false).unwrap();
+        cs.write(&frame).unwrap();
+        cs.write(&frame).unwrap();
+
+        assert_eq!(cs.drain().unwrap(), 2);
+        assert_eq!(cs.drain().unwrap(), 0);
+    }
+
+    #[test]
+    fn vcan_read_frames_timestamped() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_recv_own_msgs(true).unwrap();
+        cs.enable_timestamps().unwrap();
+
+        let frame = CanFrame::new(0x123, &[1], false, false).unwrap();
+        cs.write(&frame).unwrap();
+        cs.write(&frame).unwrap();
+
+        let mut buf = [(CanFrame::empty(), time::UNIX_EPOCH); 4];
+        assert_eq!(cs.read_frames_timestamped(&mut buf).unwrap(), 2);
+        assert_eq!(buf[1].0, frame);
+        assert!(buf[1].1 > time::UNIX_EPOCH);
+    }
+
+    #[test]
+    fn vcan_interface_name() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        assert_eq!(cs.interface_name().unwrap(), vcan.name());
+    }
+
+    #[test]
+    fn vcan_write_insist_deadline() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        let frame = CanFrame::new(0x123, &[1], false, false).unwrap();
+        let deadline = time::Instant::now() + time::Duration::from_millis(100);
+
+        assert!(cs.write_insist_deadline(&frame, deadline).unwrap());
+    }
+
+    #[test]
+    fn vcan_events() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_recv_own_msgs(true).unwrap();
+
+        let frame = CanFrame::new(0x123, &[1], false, false).unwrap();
+        cs.write(&frame).unwrap();
+
+        match cs.events().next().unwrap().unwrap() {
+            CanEvent::Data(f) => assert_eq!(f, frame),
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+
+    #[test]
+    fn vcan_read_into() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_recv_own_msgs(true).unwrap();
+
+        let frame = CanFrame::new(0x123, &[1, 2], false, false).unwrap();
+        cs.write(&frame).unwrap();
+
+        let mut rx = CanFrame::default();
+        cs.read_into(&mut rx).unwrap();
+        assert_eq!(rx, frame);
+    }
+
+    #[test]
+    fn vcan_bus_subscribe() {
+        let vcan = vcan!();
+        let bus = CanBus::open(vcan.name()).unwrap();
+        let a = bus.subscribe(&[CanFilter::new(0x100, 0x7FF).unwrap()]).unwrap();
+        let b = bus.subscribe(&[CanFilter::default()]).unwrap();
+
+        assert_eq!(a.interface_index().unwrap(), bus.interface_index());
+        assert_eq!(b.interface_name().unwrap(), vcan.name());
+    }
+
+    #[test]
+    fn vcan_is_nonblocking() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        assert!(!cs.is_nonblocking().unwrap());
+
+        cs.set_nonblocking(true).unwrap();
+        assert!(cs.is_nonblocking().unwrap());
+    }
+
+    #[test]
+    fn vcan_builder() {
+        let vcan = vcan!();
+        let cs = CanSocketBuilder::new()
+            .nonblocking(true)
+            .filters(&[CanFilter::new(0x100, 0x7FF).unwrap()])
+            .open(vcan.name())
+            .unwrap();
+
+        assert!(cs.is_nonblocking().unwrap());
+        assert!(CanSocketBuilder::new().open("invalid").is_err());
+    }
+
+    #[test]
+    fn vcan_read_monotonic() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_recv_own_msgs(true).unwrap();
+
+        let before = time::Instant::now();
+        let frame = CanFrame::new(0x123, &[1], false, false).unwrap();
+        cs.write(&frame).unwrap();
+
+        let (rx, ts) = cs.read_monotonic().unwrap();
+        assert_eq!(rx, frame);
+        assert!(ts <= time::Instant::now());
+        assert!(ts + time::Duration::from_millis(100) >= before);
+    }
+
+    #[test]
+    fn vcan_replay() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        let frame = CanFrame::new(0x123, &[1], false, false).unwrap();
+        let delay = time::Duration::from_millis(20);
+
+        let start = time::Instant::now();
+        replay(&cs, vec![(delay, frame), (delay, frame)]).unwrap();
+        assert!(start.elapsed() >= 2 * delay);
+    }
+
+    #[test]
+    fn vcan_too_many_filters() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        let filters = vec![CanFilter::default(); 513];
+
+        let e = cs.set_filters(&filters).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(e.to_string(), "too many filters (got 513, max 512)");
+    }
+
+    #[test]
+    fn vcan_write_no_loopback() {
+        let vcan = vcan!();
+        let tx = CanSocket::open(vcan.name()).unwrap();
+        let rx = CanSocket::open(vcan.name()).unwrap();
+        rx.set_nonblocking(true).unwrap();
+
+        let frame = CanFrame::new(0x123, &[1], false, false).unwrap();
+        tx.write_no_loopback(&frame).unwrap();
+        assert!(rx.read().should_retry());
+
+        tx.write(&frame).unwrap();
+        assert_eq!(rx.read().unwrap().0, frame);
+    }
+
+    #[test]
+    fn vcan_reconnecting() {
+        let vcan = vcan!();
+        let mut cs = ReconnectingCanSocket::open(vcan.name(), CanSocketBuilder::new()).unwrap();
+        let frame = CanFrame::new(0x123, &[1], false, false).unwrap();
+
+        cs.write(&frame).unwrap();
+        cs.reconnect();
+        cs.write(&frame).unwrap();
+    }
+
+    #[test]
+    fn vcan_write_to() {
+        let vcan = vcan!();
+        let rx = CanSocket::open(vcan.name()).unwrap();
+        let any = CanSocket::open_any().unwrap();
+        let frame = CanFrame::new(0x123, &[1], false, false).unwrap();
+
+        any.write_to(&frame, rx.interface_index().unwrap()).unwrap();
+        assert_eq!(rx.read().unwrap().0, frame);
+    }
+
+    #[test]
+    fn vcan_j1939() {
+        let vcan = vcan!();
+        let a = J1939Socket::open(vcan.name(), 0x20).unwrap();
+        let b = J1939Socket::open(vcan.name(), 0x30).unwrap();
+
+        let data: Vec<u8> = (0..20).collect();
+        a.send(0xEF00, 0x30, &data).unwrap();
+
+        let msg = b.recv().unwrap();
+        assert_eq!(msg.pgn, 0xEF00);
+        assert_eq!(msg.source, 0x20);
+        assert_eq!(msg.data, data);
+    }
+
+    #[test]
+    fn vcan_enable_fd_frames() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+
+        // vcan interfaces default to the CAN FD MTU
+        assert_eq!(cs.interface_mtu().unwrap(), 72);
+        cs.enable_fd_frames().unwrap();
+    }
+
+    #[test]
+    fn vcan_read_id_only() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_recv_own_msgs(true).unwrap();
+
+        let frame = CanFrame::new(0x12345, &[1, 2, 3], false, false).unwrap();
+        cs.write(&frame).unwrap();
+        cs.write(&frame).unwrap();
+
+        assert_eq!(cs.read_id_only().unwrap(), frame.raw_id());
+        assert_eq!(cs.read().unwrap().0, frame);
+    }
+
+    #[test]
+    fn vcan_available_interfaces() {
+        let vcan = vcan!();
+        assert!(available_interfaces().unwrap().contains(&vcan.name().to_owned()));
+    }
+
+    #[test]
+    fn vcan_tx_timestamps() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.enable_tx_timestamps().unwrap();
+        assert!(cs.recv_tx_timestamp().should_retry());
+
+        let frame = CanFrame::new(0x123, &[1], false, false).unwrap();
+        cs.write(&frame).unwrap();
+
+        let ts = cs.recv_tx_timestamp().unwrap();
+        assert!(ts > time::UNIX_EPOCH);
+    }
+
+    #[test]
+    fn vcan_tx_queue_len() {
+        let vcan = vcan!();
+        let can_if = CanInterface::open(vcan.name()).unwrap();
+        assert_eq!(can_if.name(), vcan.name());
+        assert!(can_if.tx_queue_len().is_ok());
+    }
+
+    #[test]
+    fn vcan_set_mark() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_mark(42).unwrap();
+    }
+
+    #[test]
+    fn vcan_write_burst() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        let frame = CanFrame::new(0x123, &[1, 2, 3], false, false).unwrap();
+        assert_eq!(cs.write_burst(&frame, 100).unwrap(), 100);
+    }
+
+    #[test]
+    fn vcan_read_raw() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_read_timeout(time::Duration::from_millis(100)).unwrap();
+        cs.set_recv_own_msgs(true).unwrap();
+
+        let frame = CanFrame::new(0x123, &[1, 2, 3], false, false).unwrap();
+        cs.write(&frame).unwrap();
+
+        let mut buf = Vec::new();
+        assert_eq!(cs.read_raw(&mut buf).unwrap(), 16);
+        assert_eq!(CanFrame::try_from(&buf[..]).unwrap(), frame);
+    }
+
+    #[test]
+    fn vcan_try_enable_fd_frames() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        // vcan interfaces default to the CAN FD MTU
+        assert!(cs.try_enable_fd_frames().unwrap());
+    }
+
+    #[test]
+    fn vcan_write_timeout() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        let frame = CanFrame::new(0x123, &[1, 2, 3], false, false).unwrap();
+        assert!(cs.write_timeout(&frame, time::Duration::from_millis(100)).unwrap());
+    }
+
+    #[test]
+    fn vcan_open_filtered() {
+        let vcan = vcan!();
+        let cs = CanSocket::open_filtered(vcan.name(), 0x123).unwrap();
+        cs.set_read_timeout(time::Duration::from_millis(100)).unwrap();
+        cs.set_recv_own_msgs(true).unwrap();
+
+        cs.write(&CanFrame::new(0x124, &[1], false, false).unwrap()).unwrap();
+        cs.write(&CanFrame::new(0x123, &[2], false, false).unwrap()).unwrap();
+        assert_eq!(cs.read().unwrap().0.id(), 0x123);
+
+        assert!(CanSocket::open_filtered(vcan.name(), 0x2000_0000).is_err());
+    }
+
+    #[test]
+    fn vcan_read_available() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_recv_own_msgs(true).unwrap();
+        assert!(cs.read_available().unwrap().is_empty());
+
+        let frame = CanFrame::new(0x123, &[1, 2, 3], false, false).unwrap();
+        cs.write(&frame).unwrap();
+        cs.write(&frame).unwrap();
+
+        assert_eq!(cs.read_available().unwrap(), vec![frame, frame]);
+    }
+
+    #[test]
+    fn vcan_timestamp_resolution() {
+        let vcan = vcan!();
+        let mut cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_recv_own_msgs(true).unwrap();
+        cs.set_timestamp_resolution(TimestampResolution::Nanos);
+
+        let frame = CanFrame::new(0x123, &[1, 2, 3], false, false).unwrap();
+        cs.write(&frame).unwrap();
+
+        let (_, ts) = cs.read().unwrap();
+        let age = time::SystemTime::now().duration_since(ts).unwrap();
+        assert!(age < time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn vcan_tx_worker() {
+        let vcan = vcan!();
+        let (tx, worker) = TxWorker::spawn(CanSocket::open(vcan.name()).unwrap());
+        let frame = CanFrame::new(0x123, &[1, 2, 3], false, false).unwrap();
+
+        for _ in 0..100 {
+            tx.send(frame).unwrap();
+        }
+
+        drop(tx);
+        assert!(worker.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn vcan_rx_worker() {
+        let vcan = vcan!();
+        let rx = RxWorker::spawn(CanSocket::open(vcan.name()).unwrap());
+        let matching = rx.subscribe(Some(CanFilter::new(0x123, 0x7FF).unwrap()));
+        let all = rx.subscribe(None);
+
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.write(&CanFrame::new(0x124, &[1], false, false).unwrap()).unwrap();
+        cs.write(&CanFrame::new(0x123, &[2], false, false).unwrap()).unwrap();
+
+        let timeout = time::Duration::from_secs(1);
+        assert_eq!(matching.recv_timeout(timeout).unwrap().id(), 0x123);
+        assert_eq!(all.recv_timeout(timeout).unwrap().id(), 0x124);
+        assert_eq!(all.recv_timeout(timeout).unwrap().id(), 0x123);
+
+        assert!(rx.stop().is_ok());
+        assert!(all.recv().is_err());
+    }
+
+    #[test]
+    fn vcan_write_once() {
+        let vcan = vcan!();
+        // vcan has no controller and thus no one-shot mode
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        let frame = CanFrame::new(0x123, &[1, 2, 3], false, false).unwrap();
+        assert!(cs.write_once(&frame).is_err());
+        assert!(CanInterface::open(vcan.name()).unwrap().is_one_shot().is_err());
+    }
+
+    #[test]
+    fn vcan_set_priority() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_priority(6).unwrap();
+    }
+
+    #[test]
+    fn vcan_local_addr() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        let addr = cs.local_addr().unwrap();
+
+        assert_eq!(addr.if_index(), CanInterface::open(vcan.name()).unwrap().index());
+        assert_eq!(addr.rx_id(), 0);
+        assert_eq!(addr.tx_id(), 0);
+    }
+
+    #[test]
+    fn vcan_builder_loopback_disabled() {
+        let vcan = vcan!();
+        let other = CanSocket::open(vcan.name()).unwrap();
+        other.set_nonblocking(true).unwrap();
+
+        let cs = CanSocketBuilder::new()
+            .loopback(false)
+            .recv_own_msgs(true)
+            .nonblocking(true)
+            .open(vcan.name())
+            .unwrap();
+
+        // the very first frame must not be looped back
+        cs.write(&CanFrame::new(0x123, &[1], false, false).unwrap()).unwrap();
+        std::thread::sleep(time::Duration::from_millis(10));
+
+        assert!(cs.read().unwrap_err().should_retry());
+        assert!(other.read().unwrap_err().should_retry());
+    }
+
+    #[test]
+    fn vcan_write_frame_raw() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        let frame = CanFrame::new(0x123, &[1, 2, 3], false, false).unwrap();
+        assert_eq!(cs.write_frame_raw(&frame).unwrap(), 16);
+    }
+
+    #[test]
+    fn vcan_read_monitored_with_timestamps() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        cs.set_recv_own_msgs(true).unwrap();
+        cs.enable_drop_monitor().unwrap();
+        cs.enable_timestamps().unwrap();
+        cs.enable_tx_timestamps().unwrap();
+
+        let frame = CanFrame::new(0x123, &[1], false, false).unwrap();
+        cs.write(&frame).unwrap();
+
+        // all control messages fit, the drop counter is not truncated
+        assert_eq!(cs.read_monitored().unwrap(), (frame, 0));
+    }
+
+    #[test]
+    fn vcan_bridge_transform() {
+        let (a, b) = (vcan!(), vcan!());
+        let (bridge_a, bridge_b) = (CanSocket::open(a.name()).unwrap(), CanSocket::open(b.name()).unwrap());
+
+        // ends with an error once the interfaces are removed
+        let bridge = thread::spawn(move || {
+            Bridge::new(bridge_a, bridge_b)
+                .with_transform(|frame| match frame.id() {
+                    0x100 => None,
+                    0x200 => Some(CanFrame::new(0x201, frame.data(), false, false).unwrap()),
+                    _ => Some(frame),
+                })
+                .run()
+        });
+
+        let tx = CanSocket::open(a.name()).unwrap();
+        let rx = CanSocket::open(b.name()).unwrap();
+        rx.set_read_timeout(time::Duration::from_secs(1)).unwrap();
+
+        let frame = |id| CanFrame::new(id, &[1, 2], false, false).unwrap();
+        tx.write(&frame(0x100)).unwrap();
+        tx.write(&frame(0x200)).unwrap();
+        tx.write(&frame(0x300)).unwrap();
+
+        // 0x100 is dropped by the transform
+        assert_eq!(rx.read().unwrap().0, frame(0x201));
+        assert_eq!(rx.read().unwrap().0, frame(0x300));
+        rx.set_nonblocking(true).unwrap();
+        assert!(rx.read().should_retry());
+
+        drop(a);
+        assert!(bridge.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn vcan_bridge_error_queue() {
+        let (a, b) = (vcan!(), vcan!());
+        let bridge_a = CanSocket::open(a.name()).unwrap();
+        let bridge_b = CanSocket::open(b.name()).unwrap();
+
+        // queued TX timestamps must not make the bridge block on a read
+        bridge_b.enable_tx_timestamps().unwrap();
+        let bridge = thread::spawn(move || Bridge::new(bridge_a, bridge_b).run());
+
+        let tx = CanSocket::open(a.name()).unwrap();
+        let rx = CanSocket::open(a.name()).unwrap();
+        let other = CanSocket::open(b.name()).unwrap();
+        rx.set_read_timeout(time::Duration::from_secs(1)).unwrap();
+
+        // forwarding the first frame queues a timestamp on the bridge's socket
+        let first = CanFrame::new(0x123, &[1], false, false).unwrap();
+        let second = CanFrame::new(0x124, &[2], false, false).unwrap();
+        tx.write(&first).unwrap();
+        assert_eq!(rx.read().unwrap().0, first);
+        thread::sleep(time::Duration::from_millis(10));
+
+        other.write(&second).unwrap();
+        assert_eq!(rx.read().unwrap().0, second);
+
+        drop(b);
+        assert!(bridge.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn vcan_write_and_confirm() {
+        let vcan = vcan!();
+        let cs = CanSocket::open(vcan.name()).unwrap();
+        let other = CanSocket::open(vcan.name()).unwrap();
+
+        // a frame received before the echo stays queued on the socket
+        let foreign = CanFrame::new(0x100, &[1], false, false).unwrap();
+        other.write(&foreign).unwrap();
+        thread::sleep(time::Duration::from_millis(10));
+
+        let frame = CanFrame::new(0x123, &[2], false, false).unwrap();
+        assert!(cs.write_and_confirm(&frame, time::Duration::from_secs(1)).unwrap());
+        // followed by the frame, looped back from the socket that sent it
+        assert_eq!(cs.read().unwrap().0, foreign);
+        assert_eq!(cs.read().unwrap().0, frame);
+    }
 }
@@ -0,0 +1,173 @@
+//! Abstraction over sending and receiving frames.
+//!
+//! Application code written against `CanTransport` works with a real
+//! `CanSocket` as well as with a `MockCanSocket`, which allows testing it
+//! without a CAN interface. The trait is object safe, so code can also be
+//! written against `&dyn CanTransport` or `Box<dyn CanTransport>`.
+
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+
+use frame::CanFrame;
+use socket::{CanSocket, SharedCanSocket};
+
+/// Sending and receiving of CAN frames.
+pub trait CanTransport {
+    /// Blocking read a single can frame.
+    fn recv(&self) -> io::Result<CanFrame>;
+
+    /// Write a single can frame.
+    fn send(&self, frame: &CanFrame) -> io::Result<()>;
+}
+
+impl CanTransport for CanSocket {
+    fn recv(&self) -> io::Result<CanFrame> {
+        let mut frame = CanFrame::empty();
+        self.read_into(&mut frame)?;
+        Ok(frame)
+    }
+
+    fn send(&self, frame: &CanFrame) -> io::Result<()> {
+        self.write(frame)
+    }
+}
+
+impl CanTransport for SharedCanSocket {
+    fn recv(&self) -> io::Result<CanFrame> {
+        self.read().map(|(frame, _)| frame)
+    }
+
+    fn send(&self, frame: &CanFrame) -> io::Result<()> {
+        self.write(frame)
+    }
+}
+
+impl<T: CanTransport + ?Sized> CanTransport for &T {
+    fn recv(&self) -> io::Result<CanFrame> {
+        (**self).recv()
+    }
+
+    fn send(&self, frame: &CanFrame) -> io::Result<()> {
+        (**self).send(frame)
+    }
+}
+
+impl<T: CanTransport + ?Sized> CanTransport for Box<T> {
+    fn recv(&self) -> io::Result<CanFrame> {
+        (**self).recv()
+    }
+
+    fn send(&self, frame: &CanFrame) -> io::Result<()> {
+        (**self).send(frame)
+    }
+}
+
+impl<T: CanTransport + ?Sized> CanTransport for Arc<T> {
+    fn recv(&self) -> io::Result<CanFrame> {
+        (**self).recv()
+    }
+
+    fn send(&self, frame: &CanFrame) -> io::Result<()> {
+        (**self).send(frame)
+    }
+}
+
+/// `CanTransport` tunneling frames to a remote bus over UDP.
+///
+/// Every datagram carries a single frame in the 16 byte layout of
+/// `CanFrame::as_bytes`, so both ends need to share the same byte order. A
+/// gateway forwarding between a `CanSocket` and a `UdpCanTransport` gives
+/// access to a CAN bus on another host.
+#[derive(Debug)]
+pub struct UdpCanTransport {
+    socket: UdpSocket,
+}
+
+impl UdpCanTransport {
+    /// Bind to `local` and exchange frames with the peer at `remote`.
+    pub fn connect<A, B>(local: A, remote: B) -> io::Result<UdpCanTransport>
+        where A: ToSocketAddrs,
+              B: ToSocketAddrs
+    {
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(remote)?;
+
+        Ok(UdpCanTransport { socket })
+    }
+
+    /// Access the underlying UDP socket, e.g. to set timeouts.
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.socket
+    }
+}
+
+impl CanTransport for UdpCanTransport {
+    fn recv(&self) -> io::Result<CanFrame> {
+        // one spare byte to detect oversized datagrams
+        let mut buf = [0; 17];
+        let len = self.socket.recv(&mut buf)?;
+
+        CanFrame::try_from(&buf[..len]).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn send(&self, frame: &CanFrame) -> io::Result<()> {
+        if frame.is_error() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "error frames cannot be transmitted"));
+        }
+
+        self.socket.send(frame.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// In-memory `CanTransport` for tests.
+///
+/// Frames queued with `push_rx` are returned by `recv` in order, frames
+/// passed to `send` are captured and can be retrieved with `take_tx`. Once
+/// the receive queue is empty, `recv` fails with `WouldBlock` just like a
+/// non-blocking socket.
+#[derive(Debug, Default)]
+pub struct MockCanSocket {
+    rx: Mutex<VecDeque<CanFrame>>,
+    tx: Mutex<Vec<CanFrame>>,
+}
+
+impl MockCanSocket {
+    pub fn new() -> MockCanSocket {
+        MockCanSocket::default()
+    }
+
+    /// Queue a frame to be returned by `recv`.
+    pub fn push_rx(&self, frame: CanFrame) {
+        self.rx.lock().unwrap().push_back(frame);
+    }
+
+    /// Remove and return all frames sent so far.
+    pub fn take_tx(&self) -> Vec<CanFrame> {
+        self.tx.lock().unwrap().drain(..).collect()
+    }
+}
+
+impl CanTransport for MockCanSocket {
+    fn recv(&self) -> io::Result<CanFrame> {
+        self.rx
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::WouldBlock))
+    }
+
+    fn send(&self, frame: &CanFrame) -> io::Result<()> {
+        if frame.is_error() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "error frames cannot be transmitted"));
+        }
+
+        self.tx.lock().unwrap().push(*frame);
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 use std::{io, ptr, mem, time};
 
+use errors::CanSocketOpenError;
+
 /// `setsockopt` wrapper
 ///
 /// The libc `setsockopt` function is set to set various options on a socket.
@@ -38,6 +40,40 @@ pub fn set_socket_option<T>(fd: libc::c_int,
     Ok(())
 }
 
+/// `getsockopt` wrapper
+///
+/// Counterpart to `set_socket_option`, reads back the value of a socket
+/// option. As with setting, `T` must match the type the option expects.
+pub fn get_socket_option<T: Copy>(fd: libc::c_int,
+                                  level: libc::c_int,
+                                  name: libc::c_int) -> io::Result<T> {
+    let mut val = mem::MaybeUninit::<T>::uninit();
+    let mut len = mem::size_of::<T>() as libc::socklen_t;
+
+    let r = unsafe {
+        libc::getsockopt(fd,
+                         level,
+                         name,
+                         val.as_mut_ptr() as *mut libc::c_void,
+                         &mut len)
+    };
+
+    if r != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if len as usize != mem::size_of::<T>() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  "unexpected socket option size"));
+    }
+
+    Ok(unsafe { val.assume_init() })
+}
+
+/// `setsockopt` wrapper for options taking an array of values
+///
+/// An empty slice is passed as a null pointer with length 0. Note that for
+/// `CAN_RAW_FILTER` the kernel interprets this as "receive no frames".
 pub fn set_socket_option_mult<T>(fd: libc::c_int,
                                  level: libc::c_int,
                                  name: libc::c_int,
@@ -68,6 +104,72 @@ pub fn set_socket_option_mult<T>(fd: libc::c_int,
     Ok(())
 }
 
+/// Find a control message received by `recvmsg`
+///
+/// Walks the control messages of `msg` and returns the payload of the last
+/// one matching `level` and `ty`, interpreted as a `T`.
+pub fn find_cmsg<T: Copy>(msg: &libc::msghdr, level: libc::c_int, ty: libc::c_int) -> Option<T> {
+    let mut found = None;
+
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(msg);
+
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == level && (*cmsg).cmsg_type == ty {
+                found = Some(ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const T));
+            }
+
+            cmsg = libc::CMSG_NXTHDR(msg, cmsg);
+        }
+    }
+
+    found
+}
+
+/// Check for a full transmit queue.
+///
+/// Writing to a CAN interface whose queue is full fails with `ENOBUFS`
+/// rather than `EAGAIN`, even on blocking sockets, so `ShouldRetry` does not
+/// cover it. The queue drains as the bus accepts frames.
+pub fn is_tx_queue_full(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(libc::ENOBUFS)
+}
+
+/// Wait until `fd` is ready for any of `events` (e.g. `libc::POLLIN`).
+///
+/// Returns `Ok(false)` if the timeout expired first. Polling interrupted by
+/// a signal (`EINTR`) is resumed with the remaining time.
+pub fn poll_fd(fd: libc::c_int, events: libc::c_short, timeout: time::Duration) -> io::Result<bool> {
+    let mut pfd = libc::pollfd {
+        fd,
+        events,
+        revents: 0,
+    };
+
+    // no deadline for timeouts too large to add, they never expire anyway
+    let deadline = time::Instant::now().checked_add(timeout);
+
+    loop {
+        let remaining = match deadline {
+            Some(deadline) => deadline.saturating_duration_since(time::Instant::now()),
+            None => timeout,
+        };
+
+        let ms = remaining.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+        let r = unsafe { libc::poll(&mut pfd, 1, ms) };
+
+        if r == -1 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(e);
+        }
+
+        return Ok(r > 0);
+    }
+}
+
 pub fn timeval_from_duration(t: std::time::Duration) -> libc::timeval {
     libc::timeval {
         tv_sec: t.as_secs() as libc::time_t,
@@ -82,3 +184,17 @@ pub fn duration_from_timespec(ts: libc::timespec) -> time::Duration {
 pub fn system_time_from_timespec(ts: libc::timespec) -> time::SystemTime {
     time::UNIX_EPOCH + duration_from_timespec(ts)
 }
+
+/// Look up the index of a network interface by name.
+///
+/// Unlike `nix::net::if_::if_nametoindex`, reports a missing interface as
+/// `CanSocketOpenError::InterfaceNotFound`.
+pub fn if_nametoindex(ifname: &str) -> Result<libc::c_uint, CanSocketOpenError> {
+    match nix::net::if_::if_nametoindex(ifname) {
+        Ok(if_index) => Ok(if_index),
+        Err(nix::Error::Sys(nix::errno::ENODEV)) => {
+            Err(CanSocketOpenError::InterfaceNotFound(ifname.to_owned()))
+        }
+        Err(e) => Err(CanSocketOpenError::LookupError(e)),
+    }
+}
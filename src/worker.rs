@@ -0,0 +1,254 @@
+//! Reading and writing frames from dedicated threads.
+
+use std::{io, thread, time};
+use std::os::unix::io::AsRawFd;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+
+use filter::CanFilter;
+use frame::CanFrame;
+use socket::CanSocket;
+use task::ShouldRetry;
+use util::{is_tx_queue_full, poll_fd};
+
+/// Number of frames `TxWorker::spawn` queues before `TxHandle::send` blocks.
+pub const DEFAULT_TX_CAPACITY: usize = 64;
+
+/// Number of frames queued for an `RxWorker` subscriber before frames are
+/// dropped.
+pub const DEFAULT_RX_CAPACITY: usize = 256;
+
+// how often the RX worker checks whether it should stop
+const RX_STOP_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+// how long the TX worker waits before retrying a write to a full TX queue,
+// POLLOUT does not wait for the queue to drain
+const TX_QUEUE_FULL_BACKOFF: time::Duration = time::Duration::from_millis(2);
+
+/// A background thread owning a socket and writing the frames sent to it.
+///
+/// The worker exits once every `TxHandle` has been dropped, after writing
+/// all frames still queued. Its `JoinHandle` then returns the socket. Writes
+/// to a full TX queue (`ENOBUFS`) are retried after a short pause. If a
+/// write fails otherwise, the worker stops and the `JoinHandle` returns the
+/// error; further sends fail with `BrokenPipe`.
+///
+/// ```no_run
+/// use socketcan::frame::CanFrame;
+/// use socketcan::socket::CanSocket;
+/// use socketcan::worker::TxWorker;
+///
+/// let (tx, worker) = TxWorker::spawn(CanSocket::open("vcan0").unwrap());
+/// tx.send(CanFrame::new(0x123, &[1, 2, 3], false, false).unwrap()).unwrap();
+///
+/// drop(tx);
+/// let socket = worker.join().unwrap().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct TxWorker {
+    socket: CanSocket,
+    rx: Receiver<CanFrame>,
+}
+
+impl TxWorker {
+    /// Spawn a worker queueing up to `DEFAULT_TX_CAPACITY` frames.
+    pub fn spawn(socket: CanSocket) -> (TxHandle, thread::JoinHandle<io::Result<CanSocket>>) {
+        TxWorker::spawn_with_capacity(socket, DEFAULT_TX_CAPACITY)
+    }
+
+    /// Spawn a worker queueing up to `capacity` frames.
+    ///
+    /// Once the queue is full, `TxHandle::send` blocks until the worker
+    /// catches up.
+    pub fn spawn_with_capacity(socket: CanSocket, capacity: usize)
+                               -> (TxHandle, thread::JoinHandle<io::Result<CanSocket>>) {
+        let (tx, rx) = mpsc::sync_channel(capacity);
+        let worker = TxWorker { socket, rx };
+
+        (TxHandle { tx }, thread::spawn(move || worker.run()))
+    }
+
+    fn run(self) -> io::Result<CanSocket> {
+        // ends once all handles are dropped and the queue is empty
+        for frame in self.rx.iter() {
+            loop {
+                match self.socket.write(&frame) {
+                    Ok(()) => break,
+                    Err(ref e) if is_tx_queue_full(e) => {
+                        thread::sleep(TX_QUEUE_FULL_BACKOFF);
+                        continue;
+                    }
+                    Err(e) => {
+                        if !e.should_retry() {
+                            return Err(e);
+                        }
+                    }
+                }
+
+                poll_fd(self.socket.as_raw_fd(), libc::POLLOUT, time::Duration::from_millis(100))?;
+            }
+        }
+
+        Ok(self.socket)
+    }
+}
+
+/// Queue frames for a `TxWorker`, can be cloned to send from several threads.
+#[derive(Debug, Clone)]
+pub struct TxHandle {
+    tx: SyncSender<CanFrame>,
+}
+
+impl TxHandle {
+    /// Queue a frame, blocking while the queue is full.
+    pub fn send(&self, frame: CanFrame) -> io::Result<()> {
+        self.tx.send(frame).map_err(|_| stopped())
+    }
+
+    /// Queue a frame without blocking.
+    ///
+    /// Returns `Ok(false)` if the queue is full.
+    pub fn try_send(&self, frame: CanFrame) -> io::Result<bool> {
+        match self.tx.try_send(frame) {
+            Ok(()) => Ok(true),
+            Err(TrySendError::Full(_)) => Ok(false),
+            Err(TrySendError::Disconnected(_)) => Err(stopped()),
+        }
+    }
+}
+
+fn stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "TX worker has stopped")
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    filter: Option<CanFilter>,
+    tx: SyncSender<CanFrame>,
+}
+
+/// A background thread owning a socket and distributing the frames read to
+/// any number of subscribers.
+///
+/// Every subscriber has its own bounded queue. If a subscriber does not keep
+/// up, frames for it are dropped instead of stalling the other subscribers
+/// or the socket. Subscribers are removed once their `Receiver` is dropped.
+///
+/// ```no_run
+/// use socketcan::filter::CanFilter;
+/// use socketcan::socket::CanSocket;
+/// use socketcan::worker::RxWorker;
+///
+/// let rx = RxWorker::spawn(CanSocket::open("vcan0").unwrap());
+/// let engine = rx.subscribe(Some(CanFilter::new(0x100, 0x700).unwrap()));
+/// let all = rx.subscribe(None);
+///
+/// let frame = engine.recv().unwrap();
+/// let socket = rx.stop().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct RxWorker {
+    socket: CanSocket,
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl RxWorker {
+    /// Spawn a worker reading from `socket`.
+    pub fn spawn(socket: CanSocket) -> RxHandle {
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let worker = RxWorker {
+            socket,
+            subscribers: subscribers.clone(),
+            stop: stop.clone(),
+        };
+
+        RxHandle {
+            subscribers,
+            stop,
+            thread: thread::spawn(move || worker.run()),
+        }
+    }
+
+    fn run(self) -> io::Result<CanSocket> {
+        let r = self.forward();
+
+        // disconnect all subscribers, also if the worker failed
+        self.subscribers.lock().unwrap().clear();
+        r.map(|()| self.socket)
+    }
+
+    fn forward(&self) -> io::Result<()> {
+        let mut frame = CanFrame::default();
+
+        while !self.stop.load(Ordering::Relaxed) {
+            if !poll_fd(self.socket.as_raw_fd(), libc::POLLIN, RX_STOP_INTERVAL)? {
+                continue;
+            }
+
+            match self.socket.read_into(&mut frame) {
+                Ok(()) => (),
+                Err(ref e) if e.should_retry() => continue,
+                Err(e) => return Err(e),
+            }
+
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers.retain(|sub| {
+                if sub.filter.is_some_and(|f| !f.matches(&frame)) {
+                    return true;
+                }
+
+                match sub.tx.try_send(frame) {
+                    Ok(()) | Err(TrySendError::Full(_)) => true,
+                    Err(TrySendError::Disconnected(_)) => false,
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Subscribe to the frames read by an `RxWorker`.
+#[derive(Debug)]
+pub struct RxHandle {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+    stop: Arc<AtomicBool>,
+    thread: thread::JoinHandle<io::Result<CanSocket>>,
+}
+
+impl RxHandle {
+    /// Receive all frames read from now on, or only those matching `filter`.
+    pub fn subscribe(&self, filter: Option<CanFilter>) -> Receiver<CanFrame> {
+        self.subscribe_with_capacity(filter, DEFAULT_RX_CAPACITY)
+    }
+
+    /// Like `subscribe`, queueing up to `capacity` frames.
+    pub fn subscribe_with_capacity(&self, filter: Option<CanFilter>, capacity: usize) -> Receiver<CanFrame> {
+        let (tx, rx) = mpsc::sync_channel(capacity);
+        self.subscribers.lock().unwrap().push(Subscriber { filter, tx });
+        rx
+    }
+
+    /// Check if the worker is still reading.
+    ///
+    /// The worker stops on read errors, disconnecting all subscribers.
+    /// `stop` returns the error.
+    pub fn is_running(&self) -> bool {
+        !self.thread.is_finished()
+    }
+
+    /// Stop the worker and return its socket.
+    ///
+    /// Subscribers receive no further frames, their receivers disconnect.
+    pub fn stop(self) -> io::Result<CanSocket> {
+        self.stop.store(true, Ordering::Relaxed);
+
+        self.thread
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("RX worker panicked")))
+    }
+}